@@ -1,40 +1,101 @@
+use std::collections::HashMap;
+use std::io::Write as _;
 use std::path::{Path, PathBuf};
 
 use anyhow::{Context, anyhow};
-use async_openai::Client;
-use async_openai::config::OpenAIConfig;
-use async_openai::types::{
-    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
-    ChatCompletionRequestUserMessageContent, CreateChatCompletionRequestArgs,
-};
+use reqwest::Client as HttpClient;
 use serde_json::{Value, json};
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, ChildStdin, ChildStdout, Command};
 
+/// Maximum number of model <-> tool round-trips before the loop gives up and
+/// returns whatever text the model last produced.
+const DEFAULT_MAX_STEPS: usize = 8;
+
+/// Which LLM backend to send requests to. Each provider owns how its request
+/// body is shaped and how text/tool calls are pulled back out of the
+/// response, so the agent loop itself never has to know the wire format of
+/// any one vendor's API.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ProviderKind {
+    OpenAi,
+    Anthropic,
+    Ollama,
+}
+
 #[derive(Debug, Clone)]
 pub struct ClientOptions {
+    pub provider: ProviderKind,
+    pub model: String,
+    /// Overrides the provider's default API base URL (e.g. a local Ollama endpoint).
+    pub base_url: Option<String>,
+    /// Ignored for providers that don't require one (e.g. a local Ollama endpoint).
     pub api_key: String,
     pub server_path: PathBuf,
     pub scripts_dir: PathBuf,
-    pub model: String,
     pub prompt: String,
+    /// Upper bound on model/tool round-trips in a single `run_interaction` call.
+    pub max_steps: usize,
+    /// Require an interactive y/n confirmation before running a mutating tool call.
+    pub confirm_mutations: bool,
+    /// Raw JSON recursively merged into the provider request body, so callers
+    /// can reach provider-specific knobs without us defining a superset schema.
+    pub raw_overrides: Option<Value>,
+}
+
+impl ClientOptions {
+    pub fn new(
+        provider: ProviderKind,
+        model: String,
+        api_key: String,
+        server_path: PathBuf,
+        scripts_dir: PathBuf,
+        prompt: String,
+    ) -> Self {
+        Self {
+            provider,
+            model,
+            base_url: None,
+            api_key,
+            server_path,
+            scripts_dir,
+            prompt,
+            max_steps: DEFAULT_MAX_STEPS,
+            confirm_mutations: true,
+            raw_overrides: None,
+        }
+    }
+}
+
+/// One model-requested tool invocation and what came back from the server.
+#[derive(Debug, Clone)]
+pub struct ToolStep {
+    pub tool_name: String,
+    pub arguments: Value,
+    pub result: String,
+    /// `true` if this result was served from the in-session cache instead of
+    /// re-running `osascript`.
+    pub from_cache: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct InteractionResult {
-    pub generated_script: String,
     pub initialize_response: String,
-    pub tool_response: String,
+    pub steps: Vec<ToolStep>,
+    pub final_response: String,
 }
 
 pub async fn run_interaction(opts: ClientOptions) -> anyhow::Result<InteractionResult> {
-    if opts.api_key.trim().is_empty() {
-        return Err(anyhow!("OpenAI API key is empty"));
+    if opts.api_key.trim().is_empty() && opts.provider != ProviderKind::Ollama {
+        return Err(anyhow!("API key is empty"));
     }
 
-    let config = OpenAIConfig::new().with_api_key(opts.api_key.clone());
-    let openai = Client::with_config(config);
-    let script = generate_applescript(&openai, &opts.model, &opts.prompt).await?;
+    let provider = provider_for(opts.provider);
+    let base_url = opts
+        .base_url
+        .clone()
+        .unwrap_or_else(|| provider.default_base_url().to_string());
+    let http = HttpClient::new();
 
     let mut server = McpServerProcess::spawn(&opts.server_path, &opts.scripts_dir).await?;
 
@@ -48,59 +109,858 @@ pub async fn run_interaction(opts: ClientOptions) -> anyhow::Result<InteractionR
             }),
         )
         .await?;
-    let init_response = server.read_response().await?;
+    let initialize_response = server.read_response().await?;
 
-    server
-        .send_request(
-            2,
-            "tools/call",
-            json!({
-                "name": "app.finder",
-                "arguments": {"script": script.clone()}
-            }),
-        )
-        .await?;
-    let tool_response = server.read_response().await?;
+    server.send_request(2, "tools/list", json!({})).await?;
+    let tools_response = server.read_response().await?;
+    let tools = parse_tool_descriptions(&tools_response)?;
+
+    let mut messages = vec![
+        Message::System(
+            "You automate macOS applications. Use the provided tools to inspect or control \
+             applications instead of guessing. Respond with plain text once you have a final \
+             answer for the user. Mutating scripts may require the user's confirmation before \
+             they run."
+                .to_string(),
+        ),
+        Message::User(opts.prompt.clone()),
+    ];
+
+    let mut steps = Vec::new();
+    let mut cache: HashMap<(String, String), String> = HashMap::new();
+    let mut next_id = 3u64;
+    let mut final_response = String::new();
+
+    for _ in 0..opts.max_steps.max(1) {
+        let mut body = provider.build_request(&opts.model, &messages, &tools);
+        if let Some(overrides) = &opts.raw_overrides {
+            merge_json(&mut body, overrides);
+        }
+
+        let mut request = http.post(provider.endpoint(&base_url)).json(&body);
+        for (name, value) in provider.headers(&opts.api_key) {
+            request = request.header(name, value);
+        }
+
+        let response = request.send().await?.error_for_status()?;
+        let response_body: Value = response.json().await?;
+        let turn = provider.parse_response(&response_body)?;
+
+        messages.push(Message::Assistant {
+            text: turn.text.clone(),
+            tool_calls: turn.tool_calls.clone(),
+        });
+
+        if let Some(text) = &turn.text {
+            final_response = text.clone();
+        }
+
+        if turn.tool_calls.is_empty() {
+            break;
+        }
+
+        for call in turn.tool_calls {
+            let cache_key = (call.name.clone(), call.arguments.to_string());
+
+            let (result_text, from_cache) = if let Some(cached) = cache.get(&cache_key) {
+                (cached.clone(), true)
+            } else {
+                if !is_read_only(&call.name, &call.arguments) && opts.confirm_mutations {
+                    confirm_mutation(&call.name, &call.arguments)?;
+                }
+
+                let id = next_id;
+                next_id += 1;
+                server
+                    .send_request(
+                        id,
+                        "tools/call",
+                        json!({ "name": call.name, "arguments": call.arguments }),
+                    )
+                    .await?;
+                let response = server.read_response().await?;
+                let text = extract_tool_result_text(&response)?;
+                cache.insert(cache_key, text.clone());
+                (text, false)
+            };
+
+            messages.push(Message::Tool {
+                tool_call_id: call.id.clone(),
+                content: result_text.clone(),
+            });
+
+            steps.push(ToolStep {
+                tool_name: call.name,
+                arguments: call.arguments,
+                result: result_text,
+                from_cache,
+            });
+        }
+    }
 
     server.shutdown().await;
 
     Ok(InteractionResult {
-        generated_script: script,
-        initialize_response: init_response,
-        tool_response,
+        initialize_response,
+        steps,
+        final_response,
     })
 }
 
-async fn generate_applescript(
-    client: &Client<OpenAIConfig>,
-    model: &str,
-    prompt: &str,
-) -> anyhow::Result<String> {
-    let request = CreateChatCompletionRequestArgs::default()
-        .model(model)
-        .messages([
-            ChatCompletionRequestSystemMessageArgs::default()
-                .content("You write short AppleScript bodies that can run inside a `tell application` block. Respond with AppleScript code only, no explanations.".to_string())
-                .build()?
-                .into(),
-            ChatCompletionRequestUserMessageArgs::default()
-                .content(ChatCompletionRequestUserMessageContent::Text(prompt.to_string()))
-                .build()?
-                .into(),
-        ])
-        .build()?;
-
-    let response = client.chat().create(request).await?;
-    let choice = response
-        .choices
-        .first()
-        .ok_or_else(|| anyhow!("OpenAI response contained no choices"))?;
-    let message = choice
-        .message
-        .content
-        .as_deref()
-        .ok_or_else(|| anyhow!("OpenAI response contained no message content"))?;
-    Ok(message.trim().to_string())
+/// Patterns associated with mutating AppleScript/JXA/shell actions: setting
+/// or creating things, deleting/moving/closing them, writing output, and
+/// shell constructs that run arbitrary further commands. Matching is
+/// deliberately broad (and case-insensitive) since a false negative here
+/// means a mutating call skips confirmation, while a false positive just
+/// costs an extra y/N prompt on an actually-safe read.
+const MUTATING_SCRIPT_PATTERNS: &[&str] = &[
+    "set ", "make new", "create", "delete", "remove", "duplicate", "move ", "rename",
+    "save", "write", "close", "quit", "empty", "trash", "lock", "unlock", "eject",
+    "do shell script", "run script", ">>", ">", "rm ", "mv ", "cp ", "mkdir", "rmdir",
+    "touch ", "chmod", "chown", "kill", "pkill", "shutdown", "reboot", "curl", "sudo",
+];
+
+/// Whether `tool_name`'s invocation with `arguments` is safe to run without
+/// the user's confirmation.
+///
+/// This used to trust a `read_only` boolean the model set on its own tool
+/// call, which a model (or a prompt-injected one) could simply always set
+/// to `true` to bypass confirmation on a destructive call entirely. Instead,
+/// classification is enforced here by scanning the actual `script` argument
+/// for patterns associated with mutating actions, independent of anything
+/// the caller claims about itself. Absent a `script` argument, or when a
+/// mutating pattern matches, the call is treated as mutating — the safe
+/// default for an arbitrary AppleScript/JXA/shell invocation.
+fn is_read_only(tool_name: &str, arguments: &Value) -> bool {
+    let Some(script) = arguments.get("script").and_then(Value::as_str) else {
+        return false;
+    };
+
+    // The shell tool runs arbitrary `sh -c` commands with host access; treat
+    // every invocation as mutating regardless of content.
+    if tool_name == "shell.shell" {
+        return false;
+    }
+
+    let lower = script.to_lowercase();
+    !MUTATING_SCRIPT_PATTERNS
+        .iter()
+        .any(|pattern| lower.contains(pattern))
+}
+
+fn confirm_mutation(tool_name: &str, arguments: &Value) -> anyhow::Result<()> {
+    print!("About to run mutating tool `{tool_name}` with arguments {arguments}. Continue? [y/N] ");
+    std::io::stdout().flush().ok();
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("failed to read confirmation from stdin")?;
+
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        Err(anyhow!("user declined to run mutating tool `{tool_name}`"))
+    }
+}
+
+/// Recursively merges `overrides` into `base`, with `overrides` winning on
+/// conflicting scalar/array values.
+fn merge_json(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (Value::Object(base_map), Value::Object(override_map)) => {
+            for (key, value) in override_map {
+                merge_json(base_map.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base_slot, other) => *base_slot = other.clone(),
+    }
+}
+
+/// A provider-agnostic chat message. Each `Provider` knows how to render this
+/// into its own wire format.
+#[derive(Debug, Clone)]
+enum Message {
+    System(String),
+    User(String),
+    Assistant {
+        text: Option<String>,
+        tool_calls: Vec<ModelToolCall>,
+    },
+    Tool {
+        tool_call_id: String,
+        content: String,
+    },
+}
+
+/// A tool invocation the model requested.
+#[derive(Debug, Clone)]
+struct ModelToolCall {
+    id: String,
+    name: String,
+    arguments: Value,
+}
+
+/// What a provider's response boiled down to: trailing text and/or requested
+/// tool calls.
+#[derive(Debug, Clone, Default)]
+struct ModelTurn {
+    text: Option<String>,
+    tool_calls: Vec<ModelToolCall>,
+}
+
+#[derive(Debug, Clone)]
+struct ToolInfo {
+    name: String,
+    description: String,
+    input_schema: Value,
+}
+
+/// Owns everything specific to one LLM vendor: request shape, auth headers,
+/// and how to pull text/tool calls back out of a response body.
+trait Provider: Send + Sync {
+    fn default_base_url(&self) -> &'static str;
+    fn endpoint(&self, base_url: &str) -> String;
+    fn headers(&self, api_key: &str) -> Vec<(&'static str, String)>;
+    fn build_request(&self, model: &str, messages: &[Message], tools: &[ToolInfo]) -> Value;
+    fn parse_response(&self, body: &Value) -> anyhow::Result<ModelTurn>;
+}
+
+fn provider_for(kind: ProviderKind) -> Box<dyn Provider> {
+    match kind {
+        ProviderKind::OpenAi => Box::new(OpenAiProvider),
+        ProviderKind::Anthropic => Box::new(AnthropicProvider),
+        ProviderKind::Ollama => Box::new(OllamaProvider),
+    }
+}
+
+struct OpenAiProvider;
+
+impl Provider for OpenAiProvider {
+    fn default_base_url(&self) -> &'static str {
+        "https://api.openai.com/v1"
+    }
+
+    fn endpoint(&self, base_url: &str) -> String {
+        format!("{base_url}/chat/completions")
+    }
+
+    fn headers(&self, api_key: &str) -> Vec<(&'static str, String)> {
+        vec![("Authorization", format!("Bearer {api_key}"))]
+    }
+
+    fn build_request(&self, model: &str, messages: &[Message], tools: &[ToolInfo]) -> Value {
+        let messages: Vec<Value> = messages
+            .iter()
+            .map(|message| match message {
+                Message::System(content) => json!({ "role": "system", "content": content }),
+                Message::User(content) => json!({ "role": "user", "content": content }),
+                Message::Assistant { text, tool_calls } => json!({
+                    "role": "assistant",
+                    "content": text,
+                    "tool_calls": tool_calls.iter().map(|call| json!({
+                        "id": call.id,
+                        "type": "function",
+                        "function": {
+                            "name": call.name,
+                            "arguments": call.arguments.to_string(),
+                        }
+                    })).collect::<Vec<_>>(),
+                }),
+                Message::Tool {
+                    tool_call_id,
+                    content,
+                } => json!({
+                    "role": "tool",
+                    "tool_call_id": tool_call_id,
+                    "content": content,
+                }),
+            })
+            .collect();
+
+        let tools: Vec<Value> = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.input_schema,
+                    }
+                })
+            })
+            .collect();
+
+        json!({ "model": model, "messages": messages, "tools": tools })
+    }
+
+    fn parse_response(&self, body: &Value) -> anyhow::Result<ModelTurn> {
+        let message = body
+            .get("choices")
+            .and_then(|choices| choices.get(0))
+            .and_then(|choice| choice.get("message"))
+            .ok_or_else(|| anyhow!("OpenAI response contained no choices"))?;
+
+        let text = message
+            .get("content")
+            .and_then(|content| content.as_str())
+            .map(str::to_string);
+
+        let tool_calls = message
+            .get("tool_calls")
+            .and_then(|calls| calls.as_array())
+            .map(|calls| {
+                calls
+                    .iter()
+                    .map(|call| {
+                        let arguments_str = call
+                            .pointer("/function/arguments")
+                            .and_then(|value| value.as_str())
+                            .unwrap_or("{}");
+                        ModelToolCall {
+                            id: call
+                                .get("id")
+                                .and_then(|id| id.as_str())
+                                .unwrap_or_default()
+                                .to_string(),
+                            name: call
+                                .pointer("/function/name")
+                                .and_then(|name| name.as_str())
+                                .unwrap_or_default()
+                                .to_string(),
+                            arguments: serde_json::from_str(arguments_str).unwrap_or(json!({})),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(ModelTurn { text, tool_calls })
+    }
+}
+
+struct AnthropicProvider;
+
+impl Provider for AnthropicProvider {
+    fn default_base_url(&self) -> &'static str {
+        "https://api.anthropic.com"
+    }
+
+    fn endpoint(&self, base_url: &str) -> String {
+        format!("{base_url}/v1/messages")
+    }
+
+    fn headers(&self, api_key: &str) -> Vec<(&'static str, String)> {
+        vec![
+            ("x-api-key", api_key.to_string()),
+            ("anthropic-version", "2023-06-01".to_string()),
+        ]
+    }
+
+    fn build_request(&self, model: &str, messages: &[Message], tools: &[ToolInfo]) -> Value {
+        let system = messages
+            .iter()
+            .find_map(|message| match message {
+                Message::System(content) => Some(content.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let turns: Vec<Value> = messages
+            .iter()
+            .filter_map(|message| match message {
+                Message::System(_) => None,
+                Message::User(content) => Some(json!({
+                    "role": "user",
+                    "content": [{ "type": "text", "text": content }],
+                })),
+                Message::Assistant { text, tool_calls } => {
+                    let mut content = Vec::new();
+                    if let Some(text) = text {
+                        if !text.is_empty() {
+                            content.push(json!({ "type": "text", "text": text }));
+                        }
+                    }
+                    for call in tool_calls {
+                        content.push(json!({
+                            "type": "tool_use",
+                            "id": call.id,
+                            "name": call.name,
+                            "input": call.arguments,
+                        }));
+                    }
+                    Some(json!({ "role": "assistant", "content": content }))
+                }
+                Message::Tool {
+                    tool_call_id,
+                    content,
+                } => Some(json!({
+                    "role": "user",
+                    "content": [{
+                        "type": "tool_result",
+                        "tool_use_id": tool_call_id,
+                        "content": content,
+                    }],
+                })),
+            })
+            .collect();
+
+        let tools: Vec<Value> = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "input_schema": tool.input_schema,
+                })
+            })
+            .collect();
+
+        json!({
+            "model": model,
+            "system": system,
+            "messages": turns,
+            "tools": tools,
+            "max_tokens": 4096,
+        })
+    }
+
+    fn parse_response(&self, body: &Value) -> anyhow::Result<ModelTurn> {
+        let blocks = body
+            .get("content")
+            .and_then(|content| content.as_array())
+            .ok_or_else(|| anyhow!("Anthropic response contained no content blocks"))?;
+
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+
+        for block in blocks {
+            match block.get("type").and_then(|t| t.as_str()) {
+                Some("text") => {
+                    if let Some(fragment) = block.get("text").and_then(|t| t.as_str()) {
+                        text.push_str(fragment);
+                    }
+                }
+                Some("tool_use") => tool_calls.push(ModelToolCall {
+                    id: block
+                        .get("id")
+                        .and_then(|id| id.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    name: block
+                        .get("name")
+                        .and_then(|name| name.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    arguments: block.get("input").cloned().unwrap_or(json!({})),
+                }),
+                _ => {}
+            }
+        }
+
+        Ok(ModelTurn {
+            text: (!text.is_empty()).then_some(text),
+            tool_calls,
+        })
+    }
+}
+
+struct OllamaProvider;
+
+impl Provider for OllamaProvider {
+    fn default_base_url(&self) -> &'static str {
+        "http://localhost:11434"
+    }
+
+    fn endpoint(&self, base_url: &str) -> String {
+        format!("{base_url}/api/chat")
+    }
+
+    fn headers(&self, _api_key: &str) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    fn build_request(&self, model: &str, messages: &[Message], tools: &[ToolInfo]) -> Value {
+        let messages: Vec<Value> = messages
+            .iter()
+            .map(|message| match message {
+                Message::System(content) => json!({ "role": "system", "content": content }),
+                Message::User(content) => json!({ "role": "user", "content": content }),
+                Message::Assistant { text, tool_calls } => json!({
+                    "role": "assistant",
+                    "content": text.clone().unwrap_or_default(),
+                    "tool_calls": tool_calls.iter().map(|call| json!({
+                        "function": { "name": call.name, "arguments": call.arguments }
+                    })).collect::<Vec<_>>(),
+                }),
+                Message::Tool {
+                    tool_call_id: _,
+                    content,
+                } => json!({ "role": "tool", "content": content }),
+            })
+            .collect();
+
+        let tools: Vec<Value> = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.input_schema,
+                    }
+                })
+            })
+            .collect();
+
+        json!({ "model": model, "messages": messages, "tools": tools, "stream": false })
+    }
+
+    fn parse_response(&self, body: &Value) -> anyhow::Result<ModelTurn> {
+        let message = body
+            .get("message")
+            .ok_or_else(|| anyhow!("Ollama response contained no `message`"))?;
+
+        let text = message
+            .get("content")
+            .and_then(|content| content.as_str())
+            .filter(|content| !content.is_empty())
+            .map(str::to_string);
+
+        let tool_calls = message
+            .get("tool_calls")
+            .and_then(|calls| calls.as_array())
+            .map(|calls| {
+                calls
+                    .iter()
+                    .enumerate()
+                    .map(|(index, call)| ModelToolCall {
+                        // Ollama does not assign tool-call ids; synthesize a
+                        // stable one so the result can still be threaded back.
+                        id: format!("ollama-tool-{index}"),
+                        name: call
+                            .pointer("/function/name")
+                            .and_then(|name| name.as_str())
+                            .unwrap_or_default()
+                            .to_string(),
+                        arguments: call
+                            .pointer("/function/arguments")
+                            .cloned()
+                            .unwrap_or(json!({})),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(ModelTurn { text, tool_calls })
+    }
+}
+
+fn parse_tool_descriptions(response: &str) -> anyhow::Result<Vec<ToolInfo>> {
+    let value: Value = serde_json::from_str(response).context("failed to parse tools/list response")?;
+    let tools = value
+        .get("result")
+        .and_then(|result| result.get("tools"))
+        .and_then(|tools| tools.as_array())
+        .ok_or_else(|| anyhow!("tools/list response contained no `result.tools` array"))?;
+
+    tools
+        .iter()
+        .map(|tool| {
+            Ok(ToolInfo {
+                name: tool
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow!("tool description missing `name`"))?
+                    .to_string(),
+                description: tool
+                    .get("description")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                input_schema: tool
+                    .get("input_schema")
+                    .cloned()
+                    .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+            })
+        })
+        .collect()
+}
+
+fn extract_tool_result_text(response: &str) -> anyhow::Result<String> {
+    let value: Value = serde_json::from_str(response).context("failed to parse tools/call response")?;
+
+    if let Some(error) = value.get("error") {
+        return Err(anyhow!("tool call failed: {error}"));
+    }
+
+    let content = value
+        .get("result")
+        .and_then(|result| result.get("content"))
+        .and_then(|content| content.as_array())
+        .ok_or_else(|| anyhow!("tools/call response contained no `result.content` array"))?;
+
+    let text = content
+        .iter()
+        .filter_map(|item| item.get("text").and_then(|t| t.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_json_overwrites_scalars_and_recurses_into_objects() {
+        let mut base = json!({
+            "model": "gpt-4",
+            "temperature": 0.7,
+            "nested": { "a": 1, "b": 2 },
+        });
+        let overrides = json!({
+            "temperature": 1.0,
+            "nested": { "b": 20, "c": 3 },
+        });
+
+        merge_json(&mut base, &overrides);
+
+        assert_eq!(
+            base,
+            json!({
+                "model": "gpt-4",
+                "temperature": 1.0,
+                "nested": { "a": 1, "b": 20, "c": 3 },
+            })
+        );
+    }
+
+    #[test]
+    fn merge_json_replaces_arrays_instead_of_concatenating() {
+        let mut base = json!({ "tags": ["a", "b"] });
+        let overrides = json!({ "tags": ["c"] });
+
+        merge_json(&mut base, &overrides);
+
+        assert_eq!(base, json!({ "tags": ["c"] }));
+    }
+
+    #[test]
+    fn merge_json_inserts_keys_absent_from_base() {
+        let mut base = json!({ "existing": true });
+        let overrides = json!({ "new_key": "value" });
+
+        merge_json(&mut base, &overrides);
+
+        assert_eq!(base, json!({ "existing": true, "new_key": "value" }));
+    }
+
+    fn sample_tools() -> Vec<ToolInfo> {
+        vec![ToolInfo {
+            name: "app.get_window_name".to_string(),
+            description: "Gets the frontmost window's name.".to_string(),
+            input_schema: json!({ "type": "object", "properties": {} }),
+        }]
+    }
+
+    #[test]
+    fn openai_build_request_shapes_messages_and_tools() {
+        let provider = OpenAiProvider;
+        let messages = vec![
+            Message::System("be helpful".to_string()),
+            Message::User("what's the window title?".to_string()),
+        ];
+
+        let body = provider.build_request("gpt-4", &messages, &sample_tools());
+
+        assert_eq!(body["model"], "gpt-4");
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["role"], "user");
+        assert_eq!(body["tools"][0]["function"]["name"], "app.get_window_name");
+    }
+
+    #[test]
+    fn openai_parse_response_extracts_text() {
+        let provider = OpenAiProvider;
+        let body = json!({
+            "choices": [{ "message": { "role": "assistant", "content": "done" } }]
+        });
+
+        let turn = provider.parse_response(&body).unwrap();
+
+        assert_eq!(turn.text.as_deref(), Some("done"));
+        assert!(turn.tool_calls.is_empty());
+    }
+
+    #[test]
+    fn openai_parse_response_extracts_tool_calls() {
+        let provider = OpenAiProvider;
+        let body = json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {
+                            "name": "app.get_window_name",
+                            "arguments": "{\"read_only\":true}",
+                        }
+                    }]
+                }
+            }]
+        });
+
+        let turn = provider.parse_response(&body).unwrap();
+
+        assert!(turn.text.is_none());
+        assert_eq!(turn.tool_calls.len(), 1);
+        assert_eq!(turn.tool_calls[0].name, "app.get_window_name");
+        assert_eq!(turn.tool_calls[0].arguments, json!({ "read_only": true }));
+    }
+
+    #[test]
+    fn openai_parse_response_errors_without_choices() {
+        let provider = OpenAiProvider;
+        let body = json!({});
+
+        assert!(provider.parse_response(&body).is_err());
+    }
+
+    #[test]
+    fn anthropic_build_request_hoists_system_message() {
+        let provider = AnthropicProvider;
+        let messages = vec![
+            Message::System("be helpful".to_string()),
+            Message::User("hi".to_string()),
+        ];
+
+        let body = provider.build_request("claude-3", &messages, &sample_tools());
+
+        assert_eq!(body["system"], "be helpful");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert_eq!(body["tools"][0]["name"], "app.get_window_name");
+    }
+
+    #[test]
+    fn anthropic_parse_response_extracts_text_and_tool_use() {
+        let provider = AnthropicProvider;
+        let body = json!({
+            "content": [
+                { "type": "text", "text": "checking now" },
+                {
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "app.get_window_name",
+                    "input": { "read_only": true },
+                },
+            ]
+        });
+
+        let turn = provider.parse_response(&body).unwrap();
+
+        assert_eq!(turn.text.as_deref(), Some("checking now"));
+        assert_eq!(turn.tool_calls.len(), 1);
+        assert_eq!(turn.tool_calls[0].id, "toolu_1");
+        assert_eq!(turn.tool_calls[0].arguments, json!({ "read_only": true }));
+    }
+
+    #[test]
+    fn anthropic_parse_response_errors_without_content() {
+        let provider = AnthropicProvider;
+        let body = json!({});
+
+        assert!(provider.parse_response(&body).is_err());
+    }
+
+    #[test]
+    fn ollama_build_request_sets_stream_false() {
+        let provider = OllamaProvider;
+        let messages = vec![Message::User("hi".to_string())];
+
+        let body = provider.build_request("llama3", &messages, &sample_tools());
+
+        assert_eq!(body["stream"], false);
+        assert_eq!(body["messages"][0]["role"], "user");
+    }
+
+    #[test]
+    fn ollama_parse_response_synthesizes_tool_call_ids() {
+        let provider = OllamaProvider;
+        let body = json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [
+                    { "function": { "name": "app.a", "arguments": {} } },
+                    { "function": { "name": "app.b", "arguments": {} } },
+                ]
+            }
+        });
+
+        let turn = provider.parse_response(&body).unwrap();
+
+        assert!(turn.text.is_none());
+        assert_eq!(turn.tool_calls[0].id, "ollama-tool-0");
+        assert_eq!(turn.tool_calls[1].id, "ollama-tool-1");
+    }
+
+    #[test]
+    fn ollama_parse_response_errors_without_message() {
+        let provider = OllamaProvider;
+        let body = json!({});
+
+        assert!(provider.parse_response(&body).is_err());
+    }
+
+    #[test]
+    fn is_read_only_defaults_to_mutating_without_a_script() {
+        assert!(!is_read_only("app.finder", &json!({})));
+    }
+
+    #[test]
+    fn is_read_only_ignores_a_self_reported_flag() {
+        // A model claiming `read_only: true` on a script that actually
+        // mutates must not bypass confirmation.
+        assert!(!is_read_only(
+            "app.finder",
+            &json!({ "script": "delete file 1 of folder 2", "read_only": true })
+        ));
+    }
+
+    #[test]
+    fn is_read_only_allows_scripts_matching_no_mutating_pattern() {
+        assert!(is_read_only(
+            "app.finder",
+            &json!({ "script": "return name of window 1" })
+        ));
+    }
+
+    #[test]
+    fn is_read_only_flags_scripts_matching_a_mutating_pattern() {
+        assert!(!is_read_only(
+            "app.textedit",
+            &json!({ "script": "set the content of document 1 to \"hi\"" })
+        ));
+        assert!(!is_read_only(
+            "jxa.finder",
+            &json!({ "script": "app.doShellScript('rm -rf /tmp/x')" })
+        ));
+    }
+
+    #[test]
+    fn is_read_only_treats_every_shell_tool_call_as_mutating() {
+        assert!(!is_read_only(
+            "shell.shell",
+            &json!({ "script": "echo hello" })
+        ));
+    }
 }
 
 struct McpServerProcess {
@@ -1,16 +1,20 @@
 use std::path::PathBuf;
 
 use anyhow::Context;
-use clap::Parser;
-use rust_mcp_client::client::{ClientOptions, run_interaction};
+use clap::{Parser, ValueEnum};
+use rust_mcp_client::client::{ClientOptions, ProviderKind, run_interaction};
 
 #[derive(Debug, Parser)]
 #[command(
     author,
     version,
-    about = "Sample MCP client that uses OpenAI to drive AppleScript tools"
+    about = "Sample MCP client that uses an LLM to drive AppleScript tools"
 )]
 struct Cli {
+    /// Which LLM backend to send requests to.
+    #[arg(long, value_enum, default_value_t = ProviderArg::OpenAi)]
+    provider: ProviderArg,
+
     /// Path to the MCP server executable.
     #[arg(
         long,
@@ -23,37 +27,75 @@ struct Cli {
     #[arg(long, value_name = "DIR", default_value = "../AppScripts")]
     scripts_dir: PathBuf,
 
-    /// OpenAI model to use when generating AppleScript snippets.
+    /// Model name to pass to the chosen provider.
     #[arg(long, default_value = "gpt-4.1-mini")]
     model: String,
 
+    /// Overrides the provider's default API base URL.
+    #[arg(long, value_name = "URL")]
+    base_url: Option<String>,
+
     /// Prompt describing the automation you want the LLM to translate into AppleScript.
     #[arg(long, default_value = "Return the name of the front Finder window.")]
     prompt: String,
 }
 
+/// CLI-facing mirror of `rust_mcp_client::client::ProviderKind` so the domain
+/// type doesn't need to depend on `clap`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+enum ProviderArg {
+    OpenAi,
+    Anthropic,
+    Ollama,
+}
+
+impl From<ProviderArg> for ProviderKind {
+    fn from(arg: ProviderArg) -> Self {
+        match arg {
+            ProviderArg::OpenAi => ProviderKind::OpenAi,
+            ProviderArg::Anthropic => ProviderKind::Anthropic,
+            ProviderArg::Ollama => ProviderKind::Ollama,
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    let provider: ProviderKind = cli.provider.into();
 
-    let api_key = std::env::var("OPENAI_API_KEY")
-        .context("Set the OPENAI_API_KEY environment variable to your OpenAI API key")?;
+    let api_key = match provider {
+        ProviderKind::OpenAi => std::env::var("OPENAI_API_KEY")
+            .context("Set the OPENAI_API_KEY environment variable to your OpenAI API key")?,
+        ProviderKind::Anthropic => std::env::var("ANTHROPIC_API_KEY")
+            .context("Set the ANTHROPIC_API_KEY environment variable to your Anthropic API key")?,
+        ProviderKind::Ollama => std::env::var("OLLAMA_API_KEY").unwrap_or_default(),
+    };
 
-    let result = run_interaction(ClientOptions {
+    let mut options = ClientOptions::new(
+        provider,
+        cli.model,
         api_key,
-        server_path: cli.server_path,
-        scripts_dir: cli.scripts_dir,
-        model: cli.model,
-        prompt: cli.prompt,
-    })
-    .await?;
-
-    println!(
-        "Generated AppleScript script:\n{}\n",
-        result.generated_script.trim()
+        cli.server_path,
+        cli.scripts_dir,
+        cli.prompt,
     );
+    options.base_url = cli.base_url;
+
+    let result = run_interaction(options).await?;
+
     println!("Initialize response:\n{}\n", result.initialize_response);
-    println!("tools/call response:\n{}\n", result.tool_response);
+    for (index, step) in result.steps.iter().enumerate() {
+        let cached = if step.from_cache { " (cached)" } else { "" };
+        println!(
+            "Step {}: called `{}`{cached} with {}\n  -> {}\n",
+            index + 1,
+            step.tool_name,
+            step.arguments,
+            step.result
+        );
+    }
+    println!("Final response:\n{}\n", result.final_response.trim());
 
     Ok(())
 }
@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use clap::{Parser, ValueEnum};
@@ -14,13 +15,31 @@ pub struct Cli {
     #[arg(long, value_enum, default_value_t = TransportKind::Stdio)]
     pub transport: TransportKind,
 
-    /// Path to a Unix domain socket to listen on (used with `--transport unix-socket`).
+    /// How JSON-RPC messages are framed on `--transport stdio`. Left unset,
+    /// the transport auto-detects by peeking the first non-whitespace byte.
+    #[arg(long, value_enum)]
+    pub framing: Option<FramingKind>,
+
+    /// Path to a Unix domain socket to listen on (used with `--transport
+    /// unix-socket` or `--transport daemon`; on Windows with `--transport
+    /// daemon` this is interpreted as a named pipe name).
     #[arg(long)]
     pub socket_path: Option<PathBuf>,
 
+    /// Address to bind the network gateway to (used with `--transport websocket`
+    /// or `--transport http`).
+    #[arg(long, default_value = "127.0.0.1:8787")]
+    pub bind_addr: SocketAddr,
+
     /// Location of exported AppleScript dictionaries.
     #[arg(long, value_name = "DIR", default_value = "../AppScripts")]
     pub scripts_dir: PathBuf,
+
+    /// Register the `shell.shell` tool, which runs arbitrary `sh -c` commands
+    /// with a restricted `PATH` and no inherited environment. Off by default
+    /// since any connected client gets host command execution once it's on.
+    #[arg(long)]
+    pub enable_shell_tool: bool,
 }
 
 /// Supported transport types.
@@ -28,22 +47,52 @@ pub struct Cli {
 pub enum TransportKind {
     /// Standard input/output framing using MCP's Content-Length headers.
     Stdio,
-    /// Unix domain socket (planned, not yet implemented).
+    /// Unix domain socket, always framed with stdio's `Content-Length` headers.
     #[value(name = "unix-socket")]
     UnixSocket,
+    /// JSON-RPC frames carried over a WebSocket connection.
+    #[value(name = "websocket")]
+    WebSocket,
+    /// JSON-RPC over HTTP: requests POSTed to `/rpc`, responses streamed back
+    /// over SSE (`/events`) or a `/ws` upgrade.
+    Http,
+    /// Unix domain socket (or, on Windows, a named pipe) accepting multiple
+    /// concurrent clients, for sharing one long-lived, warmed-up server.
+    Daemon,
+}
+
+/// How a transport frames individual JSON-RPC messages on the wire.
+#[derive(Clone, Debug, Copy, Eq, PartialEq, ValueEnum)]
+pub enum FramingKind {
+    /// LSP-style `Content-Length: N\r\n\r\n{body}` headers.
+    #[value(name = "content-length")]
+    ContentLength,
+    /// One compact JSON object per line, terminated by `\n`.
+    #[value(name = "line-delimited")]
+    LineDelimited,
 }
 
 impl Cli {
     /// Validate the configuration and return the desired transport kind and ancillary data.
     pub fn transport_config(&self) -> anyhow::Result<TransportConfig> {
         match self.transport {
-            TransportKind::Stdio => Ok(TransportConfig::Stdio),
+            TransportKind::Stdio => Ok(TransportConfig::Stdio {
+                framing: self.framing,
+            }),
             TransportKind::UnixSocket => {
                 let path = self.socket_path.as_ref().ok_or_else(|| {
                     anyhow::anyhow!("--socket-path is required when using --transport unix-socket")
                 })?;
                 Ok(TransportConfig::UnixSocket(path.clone()))
             }
+            TransportKind::WebSocket => Ok(TransportConfig::WebSocket(self.bind_addr)),
+            TransportKind::Http => Ok(TransportConfig::Http(self.bind_addr)),
+            TransportKind::Daemon => {
+                let path = self.socket_path.as_ref().ok_or_else(|| {
+                    anyhow::anyhow!("--socket-path is required when using --transport daemon")
+                })?;
+                Ok(TransportConfig::Daemon(path.clone()))
+            }
         }
     }
 }
@@ -51,6 +100,9 @@ impl Cli {
 /// Normalised transport configuration produced from CLI options.
 #[derive(Clone, Debug)]
 pub enum TransportConfig {
-    Stdio,
+    Stdio { framing: Option<FramingKind> },
     UnixSocket(PathBuf),
+    WebSocket(SocketAddr),
+    Http(SocketAddr),
+    Daemon(PathBuf),
 }
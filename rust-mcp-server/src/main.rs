@@ -1,14 +1,19 @@
 mod config;
 mod protocol;
+mod req_queue;
 mod server;
 mod state;
 mod tools;
 mod transport;
 
+use std::sync::Arc;
+
 use clap::Parser;
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
-use config::Cli;
+use config::{Cli, TransportConfig};
+use server::{Server, ServerTemplate};
+use state::AppState;
 use tools::load_tools;
 
 #[tokio::main]
@@ -17,12 +22,29 @@ async fn main() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
     let transport_config = cli.transport_config()?;
-    let tool_registry = load_tools(&cli.scripts_dir)?;
-    let transport = transport::create_transport(&transport_config).await?;
-    let state = state::AppState::default();
-    let server = server::Server::new(transport, state, tool_registry);
+    let tool_registry = load_tools(&cli.scripts_dir, cli.enable_shell_tool)?;
 
-    server.run().await
+    match transport_config {
+        // The daemon transport hands each connection its own `Server` (fresh
+        // `AppState`/`ReqQueue`) built from a shared `ServerTemplate`, rather
+        // than funnelling every client through one `Server`.
+        TransportConfig::Daemon(path) => {
+            let template = Arc::new(ServerTemplate::new(tool_registry));
+            transport::serve_daemon(&path, template).await
+        }
+        // Likewise, the HTTP/SSE/WebSocket gateway hands each `/events` or
+        // `/ws` connection its own `Server` rather than broadcasting every
+        // response to every connected client.
+        TransportConfig::Http(bind_addr) => {
+            let template = Arc::new(ServerTemplate::new(tool_registry));
+            transport::serve_http(bind_addr, template).await
+        }
+        single_connection => {
+            let transport = transport::create_transport(&single_connection).await?;
+            let server = Server::new(transport, AppState::default(), tool_registry);
+            server.run().await
+        }
+    }
 }
 
 fn initialise_tracing() {
@@ -1,8 +1,16 @@
 use serde::{Deserialize, Serialize};
 
-/// Current MCP protocol version supported by this server skeleton.
+/// Protocol version used when a client doesn't request one explicitly.
 pub const PROTOCOL_VERSION: &str = "2024-10-30";
 
+/// Every protocol version this server understands, oldest first. Versions
+/// sort lexically by their date, so comparisons like "is the negotiated
+/// version new enough for feature X" can just compare strings.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2024-10-30", "2025-01-01"];
+
+/// Protocol version that introduced `ToolResultContent.is_error`.
+pub const PROTOCOL_VERSION_TOOL_RESULT_IS_ERROR: &str = "2025-01-01";
+
 /// Generic JSON-RPC request envelope used by the protocol.
 #[derive(Debug, Deserialize)]
 pub struct RequestEnvelope {
@@ -15,7 +23,11 @@ pub struct RequestEnvelope {
 }
 
 /// Generic JSON-RPC response envelope used by the protocol.
-#[derive(Debug, Serialize)]
+///
+/// Also deserialized on the way in: when the server issues its own
+/// server-initiated request (see `server::client::ServerClient`), the
+/// client's reply arrives framed exactly like this.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ResponseEnvelope {
     #[serde(rename = "jsonrpc")]
     pub protocol: String,
@@ -118,6 +130,16 @@ pub struct ToolListResult {
     pub next_cursor: Option<String>,
 }
 
+/// Params for `notifications/cancelled`: the client no longer wants the
+/// result of an in-flight request and asks the server to stop working on it.
+#[derive(Debug, Deserialize)]
+pub struct CancelledParams {
+    #[serde(rename = "requestId")]
+    pub request_id: serde_json::Value,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ToolCallParams {
     pub name: String,
@@ -135,6 +157,10 @@ pub struct ToolResultContent {
     #[serde(rename = "type")]
     pub r#type: String,
     pub text: String,
+    /// Only emitted when the negotiated protocol version is
+    /// [`PROTOCOL_VERSION_TOOL_RESULT_IS_ERROR`] or newer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_error: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Tracks in-flight incoming requests by their JSON-RPC `id`, so a
+/// `notifications/cancelled` message can cooperatively abort the matching
+/// handler instead of leaving it to run to completion.
+#[derive(Clone, Default)]
+pub struct ReqQueue {
+    inflight: Arc<Mutex<HashMap<String, CancellationToken>>>,
+}
+
+impl ReqQueue {
+    /// Registers `id` as in-flight and returns the token its handler should
+    /// race against.
+    pub async fn register(&self, id: &serde_json::Value) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.inflight
+            .lock()
+            .await
+            .insert(id_key(id), token.clone());
+        token
+    }
+
+    /// Removes `id` once its handler has finished, successfully, with an
+    /// error, or cancelled.
+    pub async fn complete(&self, id: &serde_json::Value) {
+        self.inflight.lock().await.remove(&id_key(id));
+    }
+
+    /// Cancels the in-flight request `id`, if any. Cancelling an unknown or
+    /// already-finished id is a no-op, matching MCP cancellation semantics.
+    pub async fn cancel(&self, id: &serde_json::Value) {
+        if let Some(token) = self.inflight.lock().await.get(&id_key(id)) {
+            token.cancel();
+        }
+    }
+}
+
+/// `serde_json::Value` isn't `Hash`, so requests are keyed by their
+/// canonical JSON rendering (e.g. `"1"` vs `"\"abc\""`).
+fn id_key(id: &serde_json::Value) -> String {
+    id.to_string()
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::anyhow;
+use serde_json::json;
+use tokio::sync::{Mutex, mpsc, oneshot};
+
+use crate::protocol::ResponseEnvelope;
+use crate::state::AppState;
+
+use super::WireMessage;
+
+/// Flag a client must set under `InitializeParams.capabilities.experimental`
+/// before the server will attempt a server-initiated request against it.
+const SERVER_REQUESTS_CAPABILITY: &str = "serverRequests";
+
+/// Handle for issuing server-initiated JSON-RPC requests and notifications
+/// back to the client (e.g. `sampling/createMessage`, progress updates).
+///
+/// Outbound frames share the writer task's single channel so they can never
+/// interleave with ordinary responses on the wire. A reply to one of our
+/// requests is routed back here by `Server::run`'s read loop via
+/// [`ServerClient::resolve`], which looks the `id` up in `pending` and wakes
+/// the task awaiting it.
+#[derive(Clone)]
+pub struct ServerClient {
+    wire_tx: mpsc::Sender<WireMessage>,
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<ResponseEnvelope>>>>,
+    next_id: Arc<AtomicU64>,
+    state: AppState,
+}
+
+impl ServerClient {
+    pub(super) fn new(wire_tx: mpsc::Sender<WireMessage>, state: AppState) -> Self {
+        Self {
+            wire_tx,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU64::new(1)),
+            state,
+        }
+    }
+
+    /// Sends `method` as a server-initiated request and awaits the client's response.
+    ///
+    /// Fails up front if the client never advertised
+    /// `experimental.serverRequests` at `initialize` — issuing a request a
+    /// client doesn't expect would just hang waiting for a reply that never
+    /// comes.
+    ///
+    /// No handler in this crate calls this yet (the first candidate is a
+    /// `sampling/createMessage` call site for tools that want the client to
+    /// sample from its model); it's covered by this module's tests in the
+    /// meantime so the capability doesn't bit-rot unexercised.
+    #[allow(dead_code)]
+    pub async fn request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> anyhow::Result<ResponseEnvelope> {
+        self.ensure_supported().await?;
+
+        let id = json!(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id.to_string(), tx);
+
+        let envelope = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        if self.wire_tx.send(WireMessage::Raw(envelope)).await.is_err() {
+            self.pending.lock().await.remove(&id.to_string());
+            return Err(anyhow!(
+                "transport closed before request `{method}` could be sent"
+            ));
+        }
+
+        rx.await
+            .map_err(|_| anyhow!("connection closed before a response to `{method}` arrived"))
+    }
+
+    /// Sends a fire-and-forget `notifications/*` message: no `id`, no reply expected.
+    ///
+    /// No handler in this crate calls this yet (the first candidate is a
+    /// `notifications/progress` heartbeat for long-running tool calls); see
+    /// the note on [`ServerClient::request`].
+    #[allow(dead_code)]
+    pub async fn notify(&self, method: &str, params: serde_json::Value) -> anyhow::Result<()> {
+        self.ensure_supported().await?;
+
+        let envelope = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        });
+        self.wire_tx
+            .send(WireMessage::Raw(envelope))
+            .await
+            .map_err(|_| anyhow!("transport closed before notification `{method}` could be sent"))
+    }
+
+    /// Routes an incoming `ResponseEnvelope` that answers one of our own
+    /// outbound requests to the task awaiting it. A response for an unknown
+    /// or already-resolved `id` is dropped silently.
+    pub(super) async fn resolve(&self, response: ResponseEnvelope) {
+        let key = response.id.to_string();
+        if let Some(tx) = self.pending.lock().await.remove(&key) {
+            let _ = tx.send(response);
+        }
+    }
+
+    async fn ensure_supported(&self) -> anyhow::Result<()> {
+        let supported = self
+            .state
+            .client_capabilities()
+            .await
+            .is_some_and(|capabilities| {
+                capabilities
+                    .get(SERVER_REQUESTS_CAPABILITY)
+                    .and_then(serde_json::Value::as_bool)
+                    .unwrap_or(false)
+            });
+
+        if supported {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "client did not advertise `experimental.{SERVER_REQUESTS_CAPABILITY}` at initialize"
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn client_with_server_requests_capability() -> (ServerClient, mpsc::Receiver<WireMessage>) {
+        let (wire_tx, wire_rx) = mpsc::channel(16);
+        let state = AppState::default();
+        state
+            .mark_initialized(
+                "2024-10-30".to_string(),
+                json!({ "serverRequests": true }),
+            )
+            .await;
+        (ServerClient::new(wire_tx, state), wire_rx)
+    }
+
+    #[tokio::test]
+    async fn request_fails_fast_without_the_server_requests_capability() {
+        let (wire_tx, _wire_rx) = mpsc::channel(16);
+        let client = ServerClient::new(wire_tx, AppState::default());
+
+        let err = client
+            .request("sampling/createMessage", json!({}))
+            .await
+            .expect_err("client never advertised experimental.serverRequests");
+        assert!(err.to_string().contains("serverRequests"));
+    }
+
+    #[tokio::test]
+    async fn notify_fails_fast_without_the_server_requests_capability() {
+        let (wire_tx, _wire_rx) = mpsc::channel(16);
+        let client = ServerClient::new(wire_tx, AppState::default());
+
+        let err = client
+            .notify("notifications/progress", json!({}))
+            .await
+            .expect_err("client never advertised experimental.serverRequests");
+        assert!(err.to_string().contains("serverRequests"));
+    }
+
+    #[tokio::test]
+    async fn notify_sends_a_raw_frame_with_no_id() {
+        let (client, mut wire_rx) = client_with_server_requests_capability().await;
+
+        client
+            .notify("notifications/progress", json!({ "progress": 1 }))
+            .await
+            .expect("capability is advertised");
+
+        let WireMessage::Raw(frame) = wire_rx.try_recv().expect("one frame should be queued") else {
+            panic!("expected a Raw frame");
+        };
+        assert_eq!(frame["method"], "notifications/progress");
+        assert!(frame.get("id").is_none());
+    }
+
+    #[tokio::test]
+    async fn request_resolves_once_resolve_is_called_with_a_matching_id() {
+        let (client, mut wire_rx) = client_with_server_requests_capability().await;
+
+        let pending = tokio::spawn({
+            let client = client.clone();
+            async move { client.request("sampling/createMessage", json!({})).await }
+        });
+
+        let WireMessage::Raw(frame) = wire_rx
+            .recv()
+            .await
+            .expect("the request frame should have been sent")
+        else {
+            panic!("expected a Raw frame");
+        };
+        let id = frame["id"].clone();
+
+        client
+            .resolve(ResponseEnvelope::success(id, json!({ "ok": true })))
+            .await;
+
+        let response = pending
+            .await
+            .expect("task should not panic")
+            .expect("resolve should have completed the pending request");
+        assert_eq!(response.result.unwrap()["ok"], true);
+    }
+
+    #[tokio::test]
+    async fn resolve_of_an_unknown_id_is_dropped_silently() {
+        let (client, _wire_rx) = client_with_server_requests_capability().await;
+
+        // No matching `request` call precedes this; must not panic.
+        client
+            .resolve(ResponseEnvelope::success(json!(999), json!({})))
+            .await;
+    }
+}
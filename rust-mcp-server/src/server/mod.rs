@@ -1,28 +1,72 @@
+use std::collections::HashSet;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
 use anyhow::{Context, anyhow};
 use serde_json::json;
+use tokio::process::Command;
+use tokio::sync::{Semaphore, mpsc};
 use tracing::{debug, info, warn};
 
 use crate::protocol::{
-    InitializeParams, InitializeResult, PROTOCOL_VERSION, RequestEnvelope, ResponseEnvelope,
-    ResponseError, ServerCapabilities, ServerInfo, ToolCallParams, ToolCallResult, ToolListParams,
-    ToolListResult, ToolResultContent,
+    CancelledParams, InitializeParams, InitializeResult, PROTOCOL_VERSION,
+    PROTOCOL_VERSION_TOOL_RESULT_IS_ERROR, RequestEnvelope, ResponseEnvelope, ResponseError,
+    ServerCapabilities, ServerInfo, SUPPORTED_PROTOCOL_VERSIONS, ToolCallParams, ToolCallResult,
+    ToolListParams, ToolListResult, ToolResultContent,
 };
+use crate::req_queue::ReqQueue;
 use crate::state::AppState;
-use crate::tools::{Tool, ToolRegistry};
+use crate::tools::{Tool, ToolKind, ToolRegistry};
 use crate::transport::BoxTransport;
-use tokio::process::Command;
 
-/// Main MCP server type that owns state, capabilities, and handles JSON-RPC traffic.
-pub struct Server {
-    transport: BoxTransport,
-    state: AppState,
+mod client;
+pub use client::ServerClient;
+
+/// Everything the writer task can be asked to put on the wire: either a
+/// JSON-RPC response to a request we received, or a raw frame (an outbound
+/// server-initiated request/notification built by [`ServerClient`]).
+enum WireMessage {
+    Response(ResponseEnvelope),
+    Raw(serde_json::Value),
+}
+
+/// Bounds how many tool calls (i.e. `osascript`/shell child processes) may be
+/// in flight at once, so a flood of requests can't fork unbounded processes.
+fn default_dispatch_permits() -> usize {
+    std::thread::available_parallelism()
+        .map(std::num::NonZeroUsize::get)
+        .unwrap_or(4)
+}
+
+/// Default per-call timeout applied when a `tools/call` doesn't specify its
+/// own `timeout_ms` argument.
+const DEFAULT_TOOL_TIMEOUT_MS: u64 = 30_000;
+
+/// The parts of a `Server` that are identical for every connection served
+/// against the same tool registry: capabilities, server info, and the
+/// supported protocol versions. Built once and shared (via `Arc`) across
+/// however many connections a multi-client transport (daemon socket, HTTP)
+/// accepts, so each connection only needs to pay for its own `AppState` and
+/// `ReqQueue` rather than re-deriving capabilities or re-scanning the tool
+/// directory.
+///
+/// This is what lets `transport::serve_daemon`/`transport::serve_http` give
+/// every client its own session instead of funnelling them through one
+/// shared `AppState` (where the first `initialize` call would poison the
+/// session for everyone else) and one shared `ReqQueue` (where two clients
+/// picking the same JSON-RPC `id` could cancel each other's in-flight
+/// requests).
+#[derive(Clone)]
+pub struct ServerTemplate {
     capabilities: ServerCapabilities,
     info: ServerInfo,
-    tool_registry: ToolRegistry,
+    tool_registry: Arc<ToolRegistry>,
+    supported_versions: Arc<HashSet<String>>,
 }
 
-impl Server {
-    pub fn new(transport: BoxTransport, state: AppState, tool_registry: ToolRegistry) -> Self {
+impl ServerTemplate {
+    pub fn new(tool_registry: ToolRegistry) -> Self {
         let capabilities = ServerCapabilities {
             tools: tool_registry.descriptions(),
             ..ServerCapabilities::default()
@@ -35,195 +79,610 @@ impl Server {
         };
 
         Self {
-            transport,
-            state,
             capabilities,
             info,
-            tool_registry,
+            tool_registry: Arc::new(tool_registry),
+            supported_versions: Arc::new(
+                SUPPORTED_PROTOCOL_VERSIONS
+                    .iter()
+                    .map(|version| version.to_string())
+                    .collect(),
+            ),
         }
     }
 
+    /// Builds a `Server` for one connection against `transport`, with its own
+    /// fresh `state` and a brand new `ReqQueue` so it can never observe or
+    /// cancel another connection's in-flight requests.
+    pub fn connect(&self, transport: BoxTransport, state: AppState) -> Server {
+        Server {
+            transport,
+            state,
+            capabilities: self.capabilities.clone(),
+            info: self.info.clone(),
+            tool_registry: self.tool_registry.clone(),
+            supported_versions: self.supported_versions.clone(),
+            req_queue: ReqQueue::default(),
+        }
+    }
+}
+
+/// Main MCP server type that owns state, capabilities, and handles JSON-RPC traffic.
+pub struct Server {
+    transport: BoxTransport,
+    state: AppState,
+    capabilities: ServerCapabilities,
+    info: ServerInfo,
+    tool_registry: Arc<ToolRegistry>,
+    supported_versions: Arc<HashSet<String>>,
+    req_queue: ReqQueue,
+}
+
+impl Server {
+    pub fn new(transport: BoxTransport, state: AppState, tool_registry: ToolRegistry) -> Self {
+        ServerTemplate::new(tool_registry).connect(transport, state)
+    }
+
     /// Entry point that pumps requests from the chosen transport until EOF.
-    pub async fn run(mut self) -> anyhow::Result<()> {
+    ///
+    /// Each incoming request is dispatched onto its own task so a slow tool
+    /// call can't stall unrelated requests; a single writer task owns the
+    /// transport's write half so concurrently completing responses never
+    /// interleave on the wire. Dispatched tasks are tracked in a `JoinSet` so
+    /// that, once the transport closes, `run` waits for every in-flight tool
+    /// call to finish (and flush its response) before returning.
+    pub async fn run(self) -> anyhow::Result<()> {
         info!("starting MCP server");
-        while let Some(frame) = self.transport.read().await? {
-            debug!(payload = frame, "received frame");
-            match serde_json::from_str::<RequestEnvelope>(&frame) {
-                Ok(request) => {
-                    if let Some(id) = request.id.clone() {
-                        if let Err(err) = self.handle_request(id, request).await {
-                            warn!(?err, "failed to handle request");
+        let Self {
+            transport,
+            state,
+            capabilities,
+            info,
+            tool_registry,
+            supported_versions,
+            req_queue,
+        } = self;
+        let (mut reader, mut writer) = transport.split();
+
+        let (wire_tx, mut wire_rx) = mpsc::channel::<WireMessage>(64);
+        let writer_task = tokio::spawn(async move {
+            while let Some(message) = wire_rx.recv().await {
+                let payload = match message {
+                    WireMessage::Response(response) => serde_json::to_string(&response),
+                    WireMessage::Raw(value) => serde_json::to_string(&value),
+                };
+                match payload {
+                    Ok(payload) => {
+                        if let Err(err) = writer.write(&payload).await {
+                            warn!(?err, "failed to write response; closing writer task");
+                            break;
                         }
-                    } else if let Err(err) = self.handle_notification(request).await {
-                        warn!(?err, "failed to handle notification");
                     }
+                    Err(err) => warn!(?err, "failed to serialize outgoing frame"),
                 }
+            }
+        });
+
+        let server_client = ServerClient::new(wire_tx.clone(), state.clone());
+        let shared = DispatchShared {
+            state,
+            capabilities,
+            info,
+            tool_registry,
+            supported_versions,
+            req_queue,
+            server_client,
+            dispatch_permits: Arc::new(Semaphore::new(default_dispatch_permits())),
+            wire_tx: wire_tx.clone(),
+        };
+        let mut dispatched = tokio::task::JoinSet::new();
+
+        while let Some(frame) = reader.read().await? {
+            debug!(payload = frame, "received frame");
+            let value: serde_json::Value = match serde_json::from_str(&frame) {
+                Ok(value) => value,
                 Err(err) => {
-                    warn!(?err, "failed to deserialize request");
+                    warn!(?err, "failed to parse frame as JSON");
+                    continue;
+                }
+            };
+
+            match value {
+                // JSON-RPC 2.0 batch: an array of requests/notifications
+                // dispatched concurrently, collected into one array response.
+                serde_json::Value::Array(elements) => {
+                    let shared = shared.clone();
+                    dispatched.spawn(async move {
+                        dispatch_batch(shared, elements).await;
+                    });
                 }
+                // A `method` field means this is a request or notification
+                // from the client; anything else is assumed to be a reply to
+                // one of our own server-initiated requests (`ServerClient`).
+                value if value.get("method").is_some() => {
+                    match serde_json::from_value::<RequestEnvelope>(value) {
+                        Ok(request) => {
+                            let shared = shared.clone();
+                            dispatched.spawn(async move {
+                                if let Some(response) = dispatch_one(&shared, request).await {
+                                    let _ =
+                                        shared.wire_tx.send(WireMessage::Response(response)).await;
+                                }
+                            });
+                        }
+                        Err(err) => warn!(?err, "failed to deserialize request"),
+                    }
+                }
+                value => match serde_json::from_value::<ResponseEnvelope>(value) {
+                    Ok(response) => {
+                        let server_client = shared.server_client.clone();
+                        dispatched.spawn(async move {
+                            server_client.resolve(response).await;
+                        });
+                    }
+                    Err(err) => {
+                        warn!(?err, "received frame that is neither a request nor a response");
+                    }
+                },
             }
         }
-        info!("transport closed; shutting down");
+
+        drop(wire_tx);
+        drop(shared);
+        info!("transport closed; draining in-flight dispatches");
+        while dispatched.join_next().await.is_some() {}
+        let _ = writer_task.await;
         Ok(())
     }
+}
+
+/// Everything a single request's handler needs, cloned cheaply (`Arc`/shared
+/// state) onto each dispatched task.
+struct RequestContext {
+    state: AppState,
+    capabilities: ServerCapabilities,
+    info: ServerInfo,
+    tool_registry: Arc<ToolRegistry>,
+    supported_versions: Arc<HashSet<String>>,
+    req_queue: ReqQueue,
+    server_client: ServerClient,
+    /// Bounds concurrent tool-call child processes; see `default_dispatch_permits`.
+    /// Only `handle_tools_call` acquires from this — ping/tools/list/cancellation
+    /// must never wait behind it, or they couldn't interrupt a saturated server.
+    dispatch_permits: Arc<Semaphore>,
+}
+
+/// Bundle of handles a connection threads onto every dispatched task, whether
+/// it's handling a standalone request or one element of a JSON-RPC batch.
+/// Cloning is cheap: every field is an `Arc`, a channel handle, or similarly
+/// shared state.
+#[derive(Clone)]
+struct DispatchShared {
+    state: AppState,
+    capabilities: ServerCapabilities,
+    info: ServerInfo,
+    tool_registry: Arc<ToolRegistry>,
+    supported_versions: Arc<HashSet<String>>,
+    req_queue: ReqQueue,
+    server_client: ServerClient,
+    dispatch_permits: Arc<Semaphore>,
+    wire_tx: mpsc::Sender<WireMessage>,
+}
 
-    async fn handle_request(
-        &mut self,
-        id: serde_json::Value,
-        request: RequestEnvelope,
-    ) -> anyhow::Result<()> {
-        match request.method.as_str() {
-            "initialize" => self.handle_initialize(id, request.params).await,
-            "ping" => self.handle_ping(id, request.params).await,
-            "tools/list" => self.handle_tools_list(id, request.params).await,
-            "tools/call" => self.handle_tools_call(id, request.params).await,
-            method => {
-                let response = ResponseEnvelope::error(
-                    id,
-                    ResponseError {
-                        code: -32601,
-                        message: format!("method '{method}' not implemented"),
-                        data: None,
-                    },
-                );
-                self.write_response(response).await
+impl DispatchShared {
+    fn context(&self) -> RequestContext {
+        RequestContext {
+            state: self.state.clone(),
+            capabilities: self.capabilities.clone(),
+            info: self.info.clone(),
+            tool_registry: self.tool_registry.clone(),
+            supported_versions: self.supported_versions.clone(),
+            req_queue: self.req_queue.clone(),
+            server_client: self.server_client.clone(),
+            dispatch_permits: self.dispatch_permits.clone(),
+        }
+    }
+}
+
+/// Dispatches a single request or notification, racing the handler against
+/// cancellation, and returns the response to send (or `None` for
+/// notifications, which get no reply).
+///
+/// No dispatch permit is acquired here: only `handle_tools_call` waits on
+/// `dispatch_permits`, around the child-process spawn itself. Acquiring one
+/// for every dispatched frame would mean `ping`/`tools/list`/
+/// `notifications/cancelled` all queue up behind in-flight tool calls once
+/// the server is at its concurrency cap — including the cancellation
+/// notification meant to interrupt those very calls.
+async fn dispatch_one(
+    shared: &DispatchShared,
+    request: RequestEnvelope,
+) -> Option<ResponseEnvelope> {
+    let ctx = shared.context();
+
+    let Some(id) = request.id.clone() else {
+        if let Err(err) = handle_notification(&ctx, request).await {
+            warn!(?err, "failed to handle notification");
+        }
+        return None;
+    };
+
+    let token = ctx.req_queue.register(&id).await;
+    let response = tokio::select! {
+        result = handle_request(&ctx, id.clone(), request) => {
+            match result {
+                Ok(response) => response,
+                Err(err) => {
+                    warn!(?err, "failed to handle request");
+                    ResponseEnvelope::error(
+                        id.clone(),
+                        ResponseError {
+                            code: -32603,
+                            message: err.to_string(),
+                            data: None,
+                        },
+                    )
+                }
             }
         }
+        _ = token.cancelled() => {
+            debug!(id = %id, "request cancelled");
+            ResponseEnvelope::error(
+                id.clone(),
+                ResponseError {
+                    code: -32800,
+                    message: "request cancelled".to_string(),
+                    data: None,
+                },
+            )
+        }
+    };
+    ctx.req_queue.complete(&id).await;
+    Some(response)
+}
+
+/// Processes one JSON-RPC 2.0 batch: every element is dispatched
+/// concurrently, and the non-notification results are written back as a
+/// single array frame — or nothing at all if every element was a
+/// notification. An empty batch is itself an "Invalid Request" per spec, and
+/// an element that doesn't parse as a request still gets an error entry in
+/// the output array rather than failing the whole batch.
+async fn dispatch_batch(shared: DispatchShared, elements: Vec<serde_json::Value>) {
+    if elements.is_empty() {
+        let error = ResponseEnvelope::error(
+            serde_json::Value::Null,
+            ResponseError {
+                code: -32600,
+                message: "Invalid Request: batch must not be empty".to_string(),
+                data: None,
+            },
+        );
+        let _ = shared.wire_tx.send(WireMessage::Response(error)).await;
+        return;
     }
 
-    async fn handle_notification(&mut self, request: RequestEnvelope) -> anyhow::Result<()> {
-        match request.method.as_str() {
-            "shutdown" => {
-                info!("client requested shutdown");
-                // Future work: trigger graceful shutdown state.
-                Ok(())
+    let mut batch = tokio::task::JoinSet::new();
+    for element in elements {
+        let shared = shared.clone();
+        batch.spawn(async move {
+            match serde_json::from_value::<RequestEnvelope>(element) {
+                Ok(request) => dispatch_one(&shared, request).await,
+                Err(err) => {
+                    warn!(?err, "malformed batch element");
+                    Some(ResponseEnvelope::error(
+                        serde_json::Value::Null,
+                        ResponseError {
+                            code: -32600,
+                            message: "Invalid Request".to_string(),
+                            data: None,
+                        },
+                    ))
+                }
             }
-            method => {
-                debug!(method, "ignoring unsupported notification");
-                Ok(())
+        });
+    }
+
+    let mut responses = Vec::new();
+    while let Some(result) = batch.join_next().await {
+        if let Ok(Some(response)) = result {
+            responses.push(response);
+        }
+    }
+
+    if !responses.is_empty() {
+        match serde_json::to_value(responses) {
+            Ok(array) => {
+                let _ = shared.wire_tx.send(WireMessage::Raw(array)).await;
             }
+            Err(err) => warn!(?err, "failed to serialize batch response"),
         }
     }
+}
 
-    async fn handle_initialize(
-        &mut self,
-        id: serde_json::Value,
-        params: serde_json::Value,
-    ) -> anyhow::Result<()> {
-        if self.state.is_initialized().await {
-            let response = ResponseEnvelope::error(
-                id,
-                ResponseError {
-                    code: -32600,
-                    message: "initialize already called".to_string(),
-                    data: None,
-                },
+async fn handle_request(
+    ctx: &RequestContext,
+    id: serde_json::Value,
+    request: RequestEnvelope,
+) -> anyhow::Result<ResponseEnvelope> {
+    match request.method.as_str() {
+        "initialize" => handle_initialize(ctx, id, request.params).await,
+        "ping" => handle_ping(id, request.params).await,
+        "tools/list" => handle_tools_list(ctx, id, request.params).await,
+        "tools/call" => handle_tools_call(ctx, id, request.params).await,
+        method => Ok(ResponseEnvelope::error(
+            id,
+            ResponseError {
+                code: -32601,
+                message: format!("method '{method}' not implemented"),
+                data: None,
+            },
+        )),
+    }
+}
+
+async fn handle_notification(ctx: &RequestContext, request: RequestEnvelope) -> anyhow::Result<()> {
+    match request.method.as_str() {
+        "shutdown" => {
+            info!("client requested shutdown");
+            // Future work: trigger graceful shutdown state.
+            Ok(())
+        }
+        "notifications/cancelled" => {
+            let params: CancelledParams = serde_json::from_value(request.params)
+                .context("failed to deserialize notifications/cancelled params")?;
+            debug!(
+                id = %params.request_id,
+                reason = ?params.reason,
+                "cancelling in-flight request"
             );
-            return self.write_response(response).await;
+            ctx.req_queue.cancel(&params.request_id).await;
+            Ok(())
+        }
+        method => {
+            debug!(method, "ignoring unsupported notification");
+            Ok(())
         }
+    }
+}
+
+async fn handle_initialize(
+    ctx: &RequestContext,
+    id: serde_json::Value,
+    params: serde_json::Value,
+) -> anyhow::Result<ResponseEnvelope> {
+    if ctx.state.is_initialized().await {
+        return Ok(ResponseEnvelope::error(
+            id,
+            ResponseError {
+                code: -32600,
+                message: "initialize already called".to_string(),
+                data: None,
+            },
+        ));
+    }
 
-        let params: InitializeParams =
-            serde_json::from_value(params).context("failed to deserialize initialize params")?;
-        info!(client = %params.client.name, "initializing session");
-        self.state.mark_initialized().await;
+    let params: InitializeParams =
+        serde_json::from_value(params).context("failed to deserialize initialize params")?;
 
-        let result = InitializeResult {
-            protocol_version: params
-                .protocol_version
-                .unwrap_or_else(|| PROTOCOL_VERSION.to_string()),
-            capabilities: ServerCapabilities {
-                tools: self.tool_registry.descriptions(),
-                ..self.capabilities.clone()
+    let requested_version = params
+        .protocol_version
+        .clone()
+        .unwrap_or_else(|| PROTOCOL_VERSION.to_string());
+    if !ctx.supported_versions.contains(&requested_version) {
+        let mut supported: Vec<&String> = ctx.supported_versions.iter().collect();
+        supported.sort();
+        return Ok(ResponseEnvelope::error(
+            id,
+            ResponseError {
+                code: -32602,
+                message: format!("unsupported protocol version `{requested_version}`"),
+                data: Some(json!({ "supported_versions": supported })),
             },
-            server_info: self.info.clone(),
-        };
+        ));
+    }
 
-        let response = ResponseEnvelope::success(id, serde_json::to_value(result)?);
-        self.write_response(response).await
-    }
-
-    async fn handle_ping(
-        &mut self,
-        id: serde_json::Value,
-        params: serde_json::Value,
-    ) -> anyhow::Result<()> {
-        let message = params
-            .get("message")
-            .and_then(|m| m.as_str())
-            .unwrap_or("pong");
-        let response = ResponseEnvelope::success(id, json!({ "message": message }));
-        self.write_response(response).await
-    }
-
-    async fn handle_tools_list(
-        &mut self,
-        id: serde_json::Value,
-        params: serde_json::Value,
-    ) -> anyhow::Result<()> {
-        let _params: ToolListParams = serde_json::from_value(params).unwrap_or_default();
-        let result = ToolListResult {
-            tools: self.tool_registry.descriptions(),
-            next_cursor: None,
-        };
-        let response = ResponseEnvelope::success(id, serde_json::to_value(result)?);
-        self.write_response(response).await
-    }
-
-    async fn handle_tools_call(
-        &mut self,
-        id: serde_json::Value,
-        params: serde_json::Value,
-    ) -> anyhow::Result<()> {
-        let params: ToolCallParams =
-            serde_json::from_value(params).context("failed to deserialize tools/call params")?;
-        let tool = self
-            .tool_registry
-            .get(&params.name)
-            .ok_or_else(|| anyhow!("unknown tool `{}`", params.name))?;
-
-        let script = params
-            .arguments
-            .get("script")
-            .and_then(|value| value.as_str())
-            .ok_or_else(|| anyhow!("tool `{}` requires a `script` string argument", params.name))?;
-
-        let output = Command::new("osascript")
-            .arg("-e")
-            .arg(build_applescript(tool, script))
-            .output()
-            .await?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            let response = ResponseEnvelope::error(
+    info!(client = %params.client.name, version = %requested_version, "initializing session");
+    ctx.state
+        .mark_initialized(requested_version.clone(), params.capabilities.experimental.clone())
+        .await;
+
+    // Server-initiated requests are only advertised back if the client
+    // opted in; see `ServerClient::ensure_supported`.
+    let supports_server_requests = params
+        .capabilities
+        .experimental
+        .get("serverRequests")
+        .and_then(serde_json::Value::as_bool)
+        .unwrap_or(false);
+    let experimental = supports_server_requests.then(|| json!({ "serverRequests": true }));
+
+    let result = InitializeResult {
+        protocol_version: requested_version,
+        capabilities: ServerCapabilities {
+            tools: ctx.tool_registry.descriptions(),
+            experimental,
+            ..ctx.capabilities.clone()
+        },
+        server_info: ctx.info.clone(),
+    };
+
+    Ok(ResponseEnvelope::success(id, serde_json::to_value(result)?))
+}
+
+async fn handle_ping(
+    id: serde_json::Value,
+    params: serde_json::Value,
+) -> anyhow::Result<ResponseEnvelope> {
+    let message = params
+        .get("message")
+        .and_then(|m| m.as_str())
+        .unwrap_or("pong");
+    Ok(ResponseEnvelope::success(id, json!({ "message": message })))
+}
+
+async fn handle_tools_list(
+    ctx: &RequestContext,
+    id: serde_json::Value,
+    params: serde_json::Value,
+) -> anyhow::Result<ResponseEnvelope> {
+    if let Some(response) = require_initialized(ctx, &id).await {
+        return Ok(response);
+    }
+
+    let _params: ToolListParams = serde_json::from_value(params).unwrap_or_default();
+    let result = ToolListResult {
+        tools: ctx.tool_registry.descriptions(),
+        next_cursor: None,
+    };
+    Ok(ResponseEnvelope::success(id, serde_json::to_value(result)?))
+}
+
+/// Returns a JSON-RPC error response if the handshake hasn't completed yet,
+/// since emitting tool shapes gated on a negotiated version makes no sense
+/// before `initialize` has run.
+async fn require_initialized(
+    ctx: &RequestContext,
+    id: &serde_json::Value,
+) -> Option<ResponseEnvelope> {
+    if ctx.state.is_initialized().await {
+        None
+    } else {
+        Some(ResponseEnvelope::error(
+            id.clone(),
+            ResponseError {
+                code: -32002,
+                message: "server not initialized; call `initialize` first".to_string(),
+                data: None,
+            },
+        ))
+    }
+}
+
+async fn handle_tools_call(
+    ctx: &RequestContext,
+    id: serde_json::Value,
+    params: serde_json::Value,
+) -> anyhow::Result<ResponseEnvelope> {
+    if let Some(response) = require_initialized(ctx, &id).await {
+        return Ok(response);
+    }
+
+    let params: ToolCallParams =
+        serde_json::from_value(params).context("failed to deserialize tools/call params")?;
+    let tool = ctx
+        .tool_registry
+        .get(&params.name)
+        .ok_or_else(|| anyhow!("unknown tool `{}`", params.name))?;
+
+    let script = params
+        .arguments
+        .get("script")
+        .and_then(|value| value.as_str())
+        .ok_or_else(|| anyhow!("tool `{}` requires a `script` string argument", params.name))?;
+    let timeout_ms = params
+        .arguments
+        .get("timeout_ms")
+        .and_then(|value| value.as_u64())
+        .unwrap_or(DEFAULT_TOOL_TIMEOUT_MS);
+
+    // Acquired here, not in `dispatch_one`, so a saturated server still
+    // dispatches `ping`/`tools/list`/`notifications/cancelled` immediately
+    // instead of queuing them behind in-flight child processes.
+    let _permit = ctx
+        .dispatch_permits
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("dispatch semaphore should never be closed");
+
+    let mut command = build_command(tool, script);
+    let child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let output = match tokio::time::timeout(Duration::from_millis(timeout_ms), child.wait_with_output())
+        .await
+    {
+        Ok(result) => result?,
+        Err(_elapsed) => {
+            return Ok(ResponseEnvelope::error(
                 id,
                 ResponseError {
-                    code: -32010,
-                    message: format!("tool `{}` execution failed", params.name),
-                    data: Some(json!({
-                        "stderr": stderr,
-                        "status": output.status.code(),
-                    })),
+                    code: -32011,
+                    message: format!("tool `{}` timed out after {timeout_ms}ms", params.name),
+                    data: None,
                 },
-            );
-            return self.write_response(response).await;
+            ));
         }
+    };
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let result = ToolCallResult {
-            content: vec![ToolResultContent {
-                r#type: "text".into(),
-                text: stdout,
-            }],
-        };
-        let response = ResponseEnvelope::success(id, serde_json::to_value(result)?);
-        self.write_response(response).await
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        return Ok(ResponseEnvelope::error(
+            id,
+            ResponseError {
+                code: -32010,
+                message: format!("tool `{}` execution failed", params.name),
+                data: Some(json!({
+                    "stderr": stderr,
+                    "status": output.status.code(),
+                })),
+            },
+        ));
     }
 
-    async fn write_response(&mut self, response: ResponseEnvelope) -> anyhow::Result<()> {
-        let payload = serde_json::to_string(&response)?;
-        self.transport.write(&payload).await
-    }
+    let supports_is_error = ctx
+        .state
+        .negotiated_version()
+        .await
+        .is_some_and(|version| version.as_str() >= PROTOCOL_VERSION_TOOL_RESULT_IS_ERROR);
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let result = ToolCallResult {
+        content: vec![ToolResultContent {
+            r#type: "text".into(),
+            text: stdout,
+            is_error: supports_is_error.then_some(false),
+        }],
+    };
+    Ok(ResponseEnvelope::success(id, serde_json::to_value(result)?))
+}
+
+/// Builds the `Command` that runs a tool's script according to its `kind`.
+///
+/// `kill_on_drop` is set so the process is terminated if the caller's
+/// `tokio::time::timeout` elapses and drops the in-flight `wait_with_output`
+/// future before it completes.
+fn build_command(tool: &Tool, script: &str) -> Command {
+    let mut command = match tool.kind {
+        ToolKind::AppleScript => {
+            let mut command = Command::new("osascript");
+            command.arg("-e").arg(build_applescript(tool, script));
+            command
+        }
+        ToolKind::Jxa => {
+            let mut command = Command::new("osascript");
+            command.arg("-l").arg("JavaScript").arg("-e").arg(script);
+            command
+        }
+        ToolKind::Shell => {
+            let mut command = Command::new("sh");
+            // No inherited environment (secrets, tokens, etc. in the
+            // server's own env must not leak to an arbitrary client
+            // script) and a minimal PATH so lookups can't be hijacked by
+            // whatever happens to be ahead of the real binaries on the
+            // server's PATH.
+            command
+                .env_clear()
+                .env("PATH", "/usr/bin:/bin:/usr/sbin:/sbin")
+                .arg("-c")
+                .arg(script);
+            command
+        }
+    };
+    command.kill_on_drop(true);
+    command
 }
 
 fn build_applescript(tool: &Tool, script: &str) -> String {
@@ -236,3 +695,182 @@ fn build_applescript(tool: &Tool, script: &str) -> String {
     block.push_str("end tell\n");
     block
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_shared() -> (DispatchShared, mpsc::Receiver<WireMessage>) {
+        let (wire_tx, wire_rx) = mpsc::channel(16);
+        let state = AppState::default();
+        let shared = DispatchShared {
+            state: state.clone(),
+            capabilities: ServerCapabilities::default(),
+            info: ServerInfo {
+                name: "test".into(),
+                version: None,
+                description: None,
+            },
+            tool_registry: Arc::new(ToolRegistry::new(Vec::new())),
+            supported_versions: Arc::new(
+                SUPPORTED_PROTOCOL_VERSIONS
+                    .iter()
+                    .map(|version| version.to_string())
+                    .collect(),
+            ),
+            req_queue: ReqQueue::default(),
+            server_client: ServerClient::new(wire_tx.clone(), state),
+            // A single permit so tests can deterministically saturate it by
+            // holding the one available permit themselves.
+            dispatch_permits: Arc::new(Semaphore::new(1)),
+            wire_tx,
+        };
+        (shared, wire_rx)
+    }
+
+    #[tokio::test]
+    async fn ping_is_not_blocked_by_a_saturated_dispatch_permit() {
+        let (shared, _wire_rx) = test_shared();
+        // Simulate every dispatch permit being held by an in-flight `tools/call`.
+        let _held = shared
+            .dispatch_permits
+            .clone()
+            .try_acquire_owned()
+            .expect("the lone permit should be free before any tool call runs");
+
+        let request = RequestEnvelope {
+            protocol: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: "ping".to_string(),
+            params: json!({}),
+        };
+
+        let response = tokio::time::timeout(Duration::from_millis(200), dispatch_one(&shared, request))
+            .await
+            .expect("ping must not wait behind the dispatch-permit semaphore")
+            .expect("ping is a request, not a notification");
+        assert_eq!(response.result.unwrap()["message"], "pong");
+    }
+
+    #[tokio::test]
+    async fn cancellation_notification_is_not_blocked_by_a_saturated_dispatch_permit() {
+        let (shared, _wire_rx) = test_shared();
+        let _held = shared
+            .dispatch_permits
+            .clone()
+            .try_acquire_owned()
+            .expect("the lone permit should be free before any tool call runs");
+
+        let id = json!(7);
+        let token = shared.req_queue.register(&id).await;
+
+        let notification = RequestEnvelope {
+            protocol: "2.0".to_string(),
+            id: None,
+            method: "notifications/cancelled".to_string(),
+            params: json!({ "requestId": id }),
+        };
+
+        tokio::time::timeout(Duration::from_millis(200), dispatch_one(&shared, notification))
+            .await
+            .expect("a cancellation notification must not wait behind the dispatch-permit semaphore");
+
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn req_queue_cancel_wakes_the_registered_token() {
+        let queue = ReqQueue::default();
+        let id = json!(42);
+
+        let token = queue.register(&id).await;
+        assert!(!token.is_cancelled());
+
+        queue.cancel(&id).await;
+
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn req_queue_cancel_of_unknown_id_is_a_no_op() {
+        let queue = ReqQueue::default();
+        // No `register` call precedes this; cancelling an unknown/already
+        // completed id must not panic.
+        queue.cancel(&json!("never-registered")).await;
+    }
+
+    #[tokio::test]
+    async fn dispatch_batch_rejects_empty_batch() {
+        let (shared, mut wire_rx) = test_shared();
+
+        dispatch_batch(shared, Vec::new()).await;
+
+        let message = wire_rx.try_recv().expect("empty batch should get a response");
+        let WireMessage::Response(response) = message else {
+            panic!("expected a single Response, not a Raw array");
+        };
+        assert_eq!(response.error.unwrap().code, -32600);
+        assert!(wire_rx.try_recv().is_err(), "no further messages expected");
+    }
+
+    #[tokio::test]
+    async fn dispatch_batch_reports_malformed_elements_without_failing_the_batch() {
+        let (shared, mut wire_rx) = test_shared();
+
+        // Missing the required `method` field.
+        let elements = vec![json!({ "jsonrpc": "2.0", "id": 1 })];
+        dispatch_batch(shared, elements).await;
+
+        let message = wire_rx.try_recv().expect("malformed element should still produce a response");
+        let WireMessage::Raw(array) = message else {
+            panic!("expected a Raw batch array");
+        };
+        let responses = array.as_array().expect("batch response should be an array");
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0]["error"]["code"], -32600);
+    }
+
+    #[tokio::test]
+    async fn dispatch_batch_mixes_valid_and_malformed_elements() {
+        let (shared, mut wire_rx) = test_shared();
+
+        let elements = vec![
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "ping", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 2 }),
+        ];
+        dispatch_batch(shared, elements).await;
+
+        let message = wire_rx.try_recv().expect("batch with one good element should respond");
+        let WireMessage::Raw(array) = message else {
+            panic!("expected a Raw batch array");
+        };
+        let responses = array.as_array().expect("batch response should be an array");
+        assert_eq!(responses.len(), 2);
+        assert!(
+            responses
+                .iter()
+                .any(|response| response.get("result").is_some()),
+            "expected the ping response among the batch results"
+        );
+        assert!(
+            responses
+                .iter()
+                .any(|response| response["error"]["code"] == -32600),
+            "expected the malformed element's error among the batch results"
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_batch_sends_nothing_for_an_all_notification_batch() {
+        let (shared, mut wire_rx) = test_shared();
+
+        // `shutdown` is a notification: it has no `id`, so it gets no reply.
+        let elements = vec![json!({ "jsonrpc": "2.0", "method": "shutdown" })];
+        dispatch_batch(shared, elements).await;
+
+        assert!(
+            wire_rx.try_recv().is_err(),
+            "a batch of only notifications should produce no response frame"
+        );
+    }
+}
@@ -10,17 +10,31 @@ pub struct AppState {
 
 #[derive(Default)]
 struct StateInner {
-    /// Example stored configuration state; extend with whatever your server needs.
-    pub initialized: bool,
+    initialized: bool,
+    /// Protocol version negotiated during `initialize`, once a session exists.
+    negotiated_version: Option<String>,
+    /// The `experimental` capabilities the client declared in
+    /// `InitializeParams.capabilities.experimental`.
+    client_capabilities: Option<serde_json::Value>,
 }
 
 impl AppState {
-    pub async fn mark_initialized(&self) {
+    pub async fn mark_initialized(&self, negotiated_version: String, client_capabilities: serde_json::Value) {
         let mut inner = self.inner.write().await;
         inner.initialized = true;
+        inner.negotiated_version = Some(negotiated_version);
+        inner.client_capabilities = Some(client_capabilities);
     }
 
     pub async fn is_initialized(&self) -> bool {
         self.inner.read().await.initialized
     }
+
+    pub async fn negotiated_version(&self) -> Option<String> {
+        self.inner.read().await.negotiated_version.clone()
+    }
+
+    pub async fn client_capabilities(&self) -> Option<serde_json::Value> {
+        self.inner.read().await.client_capabilities.clone()
+    }
 }
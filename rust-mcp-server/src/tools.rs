@@ -5,23 +5,54 @@ use serde_json::json;
 
 use crate::protocol::ToolDescription;
 
+/// How a tool's `script` argument should be executed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ToolKind {
+    /// `osascript -e '<script>'` wrapped in a `tell application` block.
+    AppleScript,
+    /// `osascript -l JavaScript -e '<script>'` (JavaScript for Automation).
+    Jxa,
+    /// An `sh -c '<script>'` invocation, not tied to any application, run with
+    /// a cleared environment and a minimal `PATH`; it is not otherwise sandboxed.
+    Shell,
+}
+
 #[derive(Debug, Clone)]
 pub struct Tool {
     pub name: String,
     pub app_name: String,
     pub description: String,
+    pub kind: ToolKind,
 }
 
 impl Tool {
-    pub fn new(app_name: String) -> Self {
+    pub fn new(app_name: String, kind: ToolKind) -> Self {
         let slug = slugify(&app_name);
-        let name = format!("app.{slug}");
-        let description =
-            format!("Execute AppleScript commands in the {app_name} application context.");
+        let (prefix, description) = match kind {
+            ToolKind::AppleScript => (
+                "app",
+                format!("Execute AppleScript commands in the {app_name} application context."),
+            ),
+            ToolKind::Jxa => (
+                "jxa",
+                format!(
+                    "Execute JavaScript-for-Automation commands in the {app_name} application context."
+                ),
+            ),
+            ToolKind::Shell => (
+                "shell",
+                format!(
+                    "Execute a shell command ({app_name}) with a restricted PATH and no \
+                     inherited environment variables."
+                ),
+            ),
+        };
+        let name = format!("{prefix}.{slug}");
         Self {
             name,
             app_name,
             description,
+            kind,
         }
     }
 
@@ -34,7 +65,11 @@ impl Tool {
                 "properties": {
                     "script": {
                         "type": "string",
-                        "description": "AppleScript commands to execute inside a 'tell application' block"
+                        "description": "Script or command to execute"
+                    },
+                    "timeout_ms": {
+                        "type": "integer",
+                        "description": "Optional per-call timeout in milliseconds (default 30000)",
                     }
                 },
                 "required": ["script"],
@@ -67,18 +102,31 @@ impl ToolRegistry {
     }
 }
 
-pub fn load_tools(scripts_dir: &Path) -> anyhow::Result<ToolRegistry> {
-    let mut names: BTreeSet<String> = BTreeSet::new();
+pub fn load_tools(scripts_dir: &Path, enable_shell_tool: bool) -> anyhow::Result<ToolRegistry> {
+    let mut applescript_names: BTreeSet<String> = BTreeSet::new();
+    let mut jxa_names: BTreeSet<String> = BTreeSet::new();
 
     if scripts_dir.exists() {
-        collect_app_names(scripts_dir, &mut names, &["pdf"])?;
+        collect_app_names(scripts_dir, &mut applescript_names, &["pdf"])?;
         let text_dir = scripts_dir.join("text");
         if text_dir.exists() {
-            collect_app_names(&text_dir, &mut names, &["txt"])?;
+            collect_app_names(&text_dir, &mut applescript_names, &["txt"])?;
+        }
+        let js_dir = scripts_dir.join("js");
+        if js_dir.exists() {
+            collect_app_names(&js_dir, &mut jxa_names, &["js"])?;
         }
     }
 
-    let tools: Vec<Tool> = names.into_iter().map(Tool::new).collect();
+    let mut tools: Vec<Tool> = applescript_names
+        .into_iter()
+        .map(|name| Tool::new(name, ToolKind::AppleScript))
+        .collect();
+    tools.extend(jxa_names.into_iter().map(|name| Tool::new(name, ToolKind::Jxa)));
+    if enable_shell_tool {
+        tools.push(Tool::new("shell".to_string(), ToolKind::Shell));
+    }
+
     Ok(ToolRegistry::new(tools))
 }
 
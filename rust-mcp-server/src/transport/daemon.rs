@@ -0,0 +1,258 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+use tracing::{info, warn};
+
+use crate::server::ServerTemplate;
+use crate::state::AppState;
+
+use super::{
+    BoxTransportReader, BoxTransportWriter, Transport, TransportReader, TransportWriter,
+    read_content_length_frame, write_content_length_frame,
+};
+
+/// Accepts connections on a Unix domain socket (or, on Windows, a named pipe)
+/// for as long as the process runs, so several agents can share one
+/// long-lived, warmed-up server instead of spawning a fresh stdio child each.
+///
+/// Each accepted connection gets its own `Server` built from `template`, with
+/// a fresh `AppState` and `ReqQueue` — so one client's `initialize` doesn't
+/// poison the session for everyone else, two clients can reuse the same
+/// JSON-RPC `id` without stepping on each other's cancellation, and a
+/// client's responses are written only to its own connection rather than
+/// broadcast to whoever else happens to be connected.
+#[cfg(not(windows))]
+pub async fn serve(socket_path: &Path, template: Arc<ServerTemplate>) -> anyhow::Result<()> {
+    use tokio::net::UnixListener;
+
+    if socket_path.exists() {
+        std::fs::remove_file(socket_path)?;
+    }
+
+    let listener = UnixListener::bind(socket_path)?;
+    info!(path = %socket_path.display(), "daemon socket listening for multiple clients");
+
+    loop {
+        let (stream, _addr) = listener.accept().await?;
+        info!("daemon client connected");
+        let (read_half, write_half) = stream.into_split();
+        spawn_connection(read_half, write_half, template.clone());
+    }
+}
+
+#[cfg(windows)]
+pub async fn serve(socket_path: &Path, template: Arc<ServerTemplate>) -> anyhow::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let pipe_name = socket_path
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("named pipe path must be valid UTF-8"))?
+        .to_string();
+    info!(pipe = %pipe_name, "daemon named pipe listening for multiple clients");
+
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(&pipe_name)?;
+
+    loop {
+        server.connect().await?;
+
+        let connected = server;
+        server = ServerOptions::new().create(&pipe_name)?;
+
+        info!("daemon client connected");
+        let (read_half, write_half) = tokio::io::split(connected);
+        spawn_connection(read_half, write_half, template.clone());
+    }
+}
+
+/// Spawns a dedicated `Server` that owns this one connection end to end, so
+/// its session state and in-flight requests never cross over to any other
+/// connected client.
+fn spawn_connection<R, W>(read_half: R, write_half: W, template: Arc<ServerTemplate>)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let transport: Box<dyn Transport> = Box::new(FramedConnection::new(read_half, write_half));
+    tokio::spawn(async move {
+        let server = template.connect(transport, AppState::default());
+        if let Err(err) = server.run().await {
+            warn!(?err, "daemon connection ended with an error");
+        }
+        info!("daemon client disconnected");
+    });
+}
+
+/// One accepted daemon connection, framed identically to `StdioTransport`/
+/// `UnixSocketTransport` (`Content-Length` headers).
+struct FramedConnection<R, W> {
+    reader: BufReader<R>,
+    writer: W,
+    buffer: Vec<u8>,
+}
+
+impl<R, W> FramedConnection<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    fn new(read_half: R, write_half: W) -> Self {
+        Self {
+            reader: BufReader::new(read_half),
+            writer: write_half,
+            buffer: Vec::with_capacity(8 * 1024),
+        }
+    }
+}
+
+#[async_trait]
+impl<R, W> Transport for FramedConnection<R, W>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        read_content_length_frame(&mut self.reader, &mut self.buffer).await
+    }
+
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        write_content_length_frame(&mut self.writer, payload).await
+    }
+
+    fn split(self: Box<Self>) -> (BoxTransportReader, BoxTransportWriter) {
+        let Self {
+            reader,
+            writer,
+            buffer,
+        } = *self;
+        (
+            Box::new(FramedConnectionReader { reader, buffer }),
+            Box::new(FramedConnectionWriter { writer }),
+        )
+    }
+}
+
+struct FramedConnectionReader<R> {
+    reader: BufReader<R>,
+    buffer: Vec<u8>,
+}
+
+#[async_trait]
+impl<R> TransportReader for FramedConnectionReader<R>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        read_content_length_frame(&mut self.reader, &mut self.buffer).await
+    }
+}
+
+struct FramedConnectionWriter<W> {
+    writer: W,
+}
+
+#[async_trait]
+impl<W> TransportWriter for FramedConnectionWriter<W>
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        write_content_length_frame(&mut self.writer, payload).await
+    }
+}
+
+#[cfg(not(windows))]
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    use serde_json::{Value, json};
+    use tokio::net::UnixStream;
+
+    use crate::tools::ToolRegistry;
+
+    use super::*;
+
+    static SOCKET_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn unique_socket_path() -> std::path::PathBuf {
+        let n = SOCKET_COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("mcp-daemon-isolation-test-{}-{n}.sock", std::process::id()))
+    }
+
+    async fn connect_with_retry(path: &Path) -> UnixStream {
+        for _ in 0..100 {
+            if let Ok(stream) = UnixStream::connect(path).await {
+                return stream;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("daemon socket at {} never became connectable", path.display());
+    }
+
+    async fn roundtrip(
+        conn: &mut BufReader<UnixStream>,
+        buffer: &mut Vec<u8>,
+        id: i64,
+        method: &str,
+        params: Value,
+    ) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let body = serde_json::to_string(&request).expect("request serializes");
+        write_content_length_frame(conn, &body)
+            .await
+            .expect("writing the request frame");
+        let response = read_content_length_frame(conn, buffer)
+            .await
+            .expect("reading the response frame")
+            .expect("connection closed before a response arrived");
+        serde_json::from_str(&response).expect("valid JSON response")
+    }
+
+    #[tokio::test]
+    async fn daemon_connections_do_not_share_initialize_state_or_responses() {
+        let socket_path = unique_socket_path();
+        let template = Arc::new(ServerTemplate::new(ToolRegistry::new(Vec::new())));
+
+        let serve_path = socket_path.clone();
+        tokio::spawn(async move {
+            let _ = serve(&serve_path, template).await;
+        });
+
+        let mut conn_a = BufReader::new(connect_with_retry(&socket_path).await);
+        let mut conn_b = BufReader::new(connect_with_retry(&socket_path).await);
+        let mut buf_a = Vec::new();
+        let mut buf_b = Vec::new();
+
+        let init_response = roundtrip(
+            &mut conn_a,
+            &mut buf_a,
+            1,
+            "initialize",
+            json!({
+                "client": { "name": "connection-a", "version": "0" },
+                "protocol_version": "2024-10-30",
+            }),
+        )
+        .await;
+        assert!(
+            init_response.get("result").is_some(),
+            "connection A's initialize should succeed: {init_response:?}"
+        );
+
+        // Connection B never called initialize; if it shared `AppState` with
+        // connection A (the bug this transport was rewritten to fix) this
+        // would succeed instead of being rejected.
+        let list_response = roundtrip(&mut conn_b, &mut buf_b, 2, "tools/list", json!({})).await;
+        assert_eq!(
+            list_response["error"]["code"], -32002,
+            "connection B must still be uninitialized: {list_response:?}"
+        );
+
+        let _ = std::fs::remove_file(&socket_path);
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_trait::async_trait;
+use axum::extract::ws::{Message as WsMessage, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::response::IntoResponse;
+use axum::response::sse::{Event, Sse};
+use axum::routing::{get, post};
+use axum::{Router, http::StatusCode};
+use futures_util::{SinkExt, Stream, StreamExt};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{info, warn};
+
+use crate::server::ServerTemplate;
+use crate::state::AppState;
+
+use super::{BoxTransportReader, BoxTransportWriter, Transport, TransportReader, TransportWriter};
+
+/// Routes a `POST /rpc` frame to the session it names, by its incoming
+/// channel. Sessions are created by `GET /events` (and removed again once
+/// that SSE stream drops) or are implicit for the lifetime of a `GET /ws`
+/// connection.
+#[derive(Clone)]
+struct GatewayState {
+    template: Arc<ServerTemplate>,
+    sessions: Arc<Mutex<HashMap<String, mpsc::Sender<String>>>>,
+    next_session_id: Arc<AtomicU64>,
+}
+
+impl GatewayState {
+    fn new(template: Arc<ServerTemplate>) -> Self {
+        Self {
+            template,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            next_session_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    fn next_session_id(&self) -> String {
+        format!("sess-{}", self.next_session_id.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Serves JSON-RPC over HTTP until the listener fails: a `POST /rpc?session=ID`
+/// endpoint accepts one frame addressed to an existing session, `GET
+/// /events` opens a new session and streams its responses back over
+/// Server-Sent Events, and `GET /ws` carries the same frames bidirectionally
+/// over a single WebSocket connection for clients that prefer it.
+///
+/// Every session (one per `/events` stream, one per `/ws` connection) gets
+/// its own `Server` built from `template`, with its own `AppState`/`ReqQueue`,
+/// so one client's `initialize` or in-flight request never leaks into
+/// another's — unlike broadcasting every response to every connected client.
+pub async fn serve(bind_addr: SocketAddr, template: Arc<ServerTemplate>) -> anyhow::Result<()> {
+    let state = GatewayState::new(template);
+
+    let app = Router::new()
+        .route("/rpc", post(handle_rpc))
+        .route("/events", get(handle_sse))
+        .route("/ws", get(handle_ws))
+        .with_state(state);
+
+    let listener = TcpListener::bind(bind_addr).await?;
+    info!(%bind_addr, "HTTP/SSE/WebSocket gateway listening");
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct RpcQuery {
+    session: String,
+}
+
+async fn handle_rpc(
+    State(state): State<GatewayState>,
+    Query(query): Query<RpcQuery>,
+    body: String,
+) -> impl IntoResponse {
+    let incoming_tx = state.sessions.lock().await.get(&query.session).cloned();
+    let Some(incoming_tx) = incoming_tx else {
+        return (StatusCode::NOT_FOUND, "unknown session; open /events first").into_response();
+    };
+
+    if incoming_tx.send(body).await.is_err() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "session is shutting down").into_response();
+    }
+    StatusCode::ACCEPTED.into_response()
+}
+
+async fn handle_sse(
+    State(state): State<GatewayState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let session_id = state.next_session_id();
+
+    let (incoming_tx, incoming_rx) = mpsc::channel(64);
+    let (outgoing_tx, outgoing_rx) = mpsc::channel(64);
+
+    state
+        .sessions
+        .lock()
+        .await
+        .insert(session_id.clone(), incoming_tx);
+
+    let transport: Box<dyn Transport> = Box::new(SessionTransport {
+        incoming_rx,
+        outgoing_tx,
+    });
+    let server = state.template.connect(transport, AppState::default());
+    tokio::spawn(async move {
+        if let Err(err) = server.run().await {
+            warn!(?err, "HTTP/SSE session ended with an error");
+        }
+    });
+
+    // The first event tells the client which `session` id to address
+    // subsequent `POST /rpc` calls to.
+    let session_event = futures_util::stream::once({
+        let session_id = session_id.clone();
+        async move { Ok(Event::default().event("session").data(session_id)) }
+    });
+    let responses = SessionGuard {
+        inner: ReceiverStream::new(outgoing_rx)
+            .map(|payload| Ok(Event::default().data(payload))),
+        sessions: state.sessions.clone(),
+        session_id,
+    };
+
+    Sse::new(session_event.chain(responses))
+}
+
+/// Wraps the per-session outgoing stream and removes the session from the
+/// shared map once the SSE connection drops (client disconnects, or the
+/// underlying `Server` finishes and closes `outgoing_tx`), so a session id
+/// can't be replayed against a dead session.
+struct SessionGuard<S> {
+    inner: S,
+    sessions: Arc<Mutex<HashMap<String, mpsc::Sender<String>>>>,
+    session_id: String,
+}
+
+impl<S> Stream for SessionGuard<S>
+where
+    S: Stream<Item = Result<Event, Infallible>> + Unpin,
+{
+    type Item = Result<Event, Infallible>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        std::pin::Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
+
+impl<S> Drop for SessionGuard<S> {
+    fn drop(&mut self) {
+        let sessions = self.sessions.clone();
+        let session_id = std::mem::take(&mut self.session_id);
+        tokio::spawn(async move {
+            sessions.lock().await.remove(&session_id);
+        });
+    }
+}
+
+async fn handle_ws(ws: WebSocketUpgrade, State(state): State<GatewayState>) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_ws_connection(socket, state))
+}
+
+async fn handle_ws_connection(socket: WebSocket, state: GatewayState) {
+    let transport: Box<dyn Transport> = Box::new(WsTransport { socket });
+    let server = state.template.connect(transport, AppState::default());
+    if let Err(err) = server.run().await {
+        warn!(?err, "HTTP/WebSocket session ended with an error");
+    }
+}
+
+/// One `/events` + `/rpc` session's transport: incoming frames arrive via
+/// `POST /rpc`, outgoing frames are forwarded to that session's own SSE
+/// stream — never to any other session.
+struct SessionTransport {
+    incoming_rx: mpsc::Receiver<String>,
+    outgoing_tx: mpsc::Sender<String>,
+}
+
+#[async_trait]
+impl Transport for SessionTransport {
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        Ok(self.incoming_rx.recv().await)
+    }
+
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        // Dropping a payload because the client's SSE stream already
+        // disconnected is fine; there's no one left to deliver it to.
+        let _ = self.outgoing_tx.send(payload.to_string()).await;
+        Ok(())
+    }
+
+    fn split(self: Box<Self>) -> (BoxTransportReader, BoxTransportWriter) {
+        let Self {
+            incoming_rx,
+            outgoing_tx,
+        } = *self;
+        (
+            Box::new(SessionReader { incoming_rx }),
+            Box::new(SessionWriter { outgoing_tx }),
+        )
+    }
+}
+
+struct SessionReader {
+    incoming_rx: mpsc::Receiver<String>,
+}
+
+#[async_trait]
+impl TransportReader for SessionReader {
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        Ok(self.incoming_rx.recv().await)
+    }
+}
+
+struct SessionWriter {
+    outgoing_tx: mpsc::Sender<String>,
+}
+
+#[async_trait]
+impl TransportWriter for SessionWriter {
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        let _ = self.outgoing_tx.send(payload.to_string()).await;
+        Ok(())
+    }
+}
+
+/// A single `/ws` connection's transport: it's already a private,
+/// full-duplex channel to exactly one client, so unlike `SessionTransport`
+/// there's no routing to do.
+struct WsTransport {
+    socket: WebSocket,
+}
+
+#[async_trait]
+impl Transport for WsTransport {
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        read_next_ws_message(&mut self.socket).await
+    }
+
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        self.socket.send(WsMessage::Text(payload.to_string())).await?;
+        Ok(())
+    }
+
+    fn split(self: Box<Self>) -> (BoxTransportReader, BoxTransportWriter) {
+        let (sink, stream) = self.socket.split();
+        (
+            Box::new(WsTransportReader { stream }),
+            Box::new(WsTransportWriter { sink }),
+        )
+    }
+}
+
+struct WsTransportReader {
+    stream: futures_util::stream::SplitStream<WebSocket>,
+}
+
+#[async_trait]
+impl TransportReader for WsTransportReader {
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        read_next_ws_message(&mut self.stream).await
+    }
+}
+
+struct WsTransportWriter {
+    sink: futures_util::stream::SplitSink<WebSocket, WsMessage>,
+}
+
+#[async_trait]
+impl TransportWriter for WsTransportWriter {
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        self.sink.send(WsMessage::Text(payload.to_string())).await?;
+        Ok(())
+    }
+}
+
+async fn read_next_ws_message<S>(stream: &mut S) -> anyhow::Result<Option<String>>
+where
+    S: Stream<Item = Result<WsMessage, axum::Error>> + Unpin,
+{
+    loop {
+        match stream.next().await {
+            None => return Ok(None),
+            Some(Ok(WsMessage::Text(text))) => return Ok(Some(text)),
+            Some(Ok(WsMessage::Binary(bytes))) => return Ok(Some(String::from_utf8(bytes)?)),
+            Some(Ok(WsMessage::Close(_))) => return Ok(None),
+            Some(Ok(WsMessage::Ping(_) | WsMessage::Pong(_))) => continue,
+            Some(Err(err)) => return Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener as StdTcpListener;
+    use std::time::Duration;
+
+    use serde_json::{Value, json};
+
+    use crate::tools::ToolRegistry;
+
+    use super::*;
+
+    /// Reserves an ephemeral port by binding a plain std listener and
+    /// immediately dropping it, so `serve` can rebind the same address. There
+    /// is an unavoidable, vanishingly small race between the drop and
+    /// `serve`'s own bind; this is the standard way to pick a free port for a
+    /// test without `serve` itself reporting which one it chose.
+    fn reserve_local_addr() -> SocketAddr {
+        let listener = StdTcpListener::bind("127.0.0.1:0").expect("failed to reserve a port");
+        listener.local_addr().expect("bound listener has a local address")
+    }
+
+    async fn wait_until_listening(addr: SocketAddr) {
+        for _ in 0..100 {
+            if tokio::net::TcpStream::connect(addr).await.is_ok() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("HTTP gateway never started listening on {addr}");
+    }
+
+    /// Reads chunks off an SSE response until one full `event: ...\ndata:
+    /// ...\n\n` block has arrived, and returns the `data:` line's payload.
+    async fn next_sse_data(response: &mut reqwest::Response) -> String {
+        let mut buf = String::new();
+        loop {
+            if let Some(pos) = buf.find("\n\n") {
+                let event_block = buf[..pos].to_string();
+                let data_line = event_block
+                    .lines()
+                    .find(|line| line.starts_with("data:"))
+                    .expect("SSE event missing a data: line");
+                return data_line.trim_start_matches("data:").trim().to_string();
+            }
+            let chunk = response
+                .chunk()
+                .await
+                .expect("reading SSE chunk")
+                .expect("SSE stream ended before a full event arrived");
+            buf.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    }
+
+    #[tokio::test]
+    async fn http_sessions_do_not_share_initialize_state_or_responses() {
+        let bind_addr = reserve_local_addr();
+        let template = Arc::new(ServerTemplate::new(ToolRegistry::new(Vec::new())));
+        tokio::spawn(async move {
+            let _ = serve(bind_addr, template).await;
+        });
+        wait_until_listening(bind_addr).await;
+
+        let client = reqwest::Client::new();
+        let mut events_a = client
+            .get(format!("http://{bind_addr}/events"))
+            .send()
+            .await
+            .expect("GET /events should succeed");
+        let session_a = next_sse_data(&mut events_a).await;
+
+        let mut events_b = client
+            .get(format!("http://{bind_addr}/events"))
+            .send()
+            .await
+            .expect("GET /events should succeed");
+        let session_b = next_sse_data(&mut events_b).await;
+
+        assert_ne!(
+            session_a, session_b,
+            "each /events connection should get its own session id"
+        );
+
+        let init_body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "client": { "name": "session-a", "version": "0" },
+                "protocol_version": "2024-10-30",
+            }
+        });
+        client
+            .post(format!("http://{bind_addr}/rpc?session={session_a}"))
+            .body(init_body.to_string())
+            .send()
+            .await
+            .expect("POST /rpc should succeed");
+        let init_response: Value =
+            serde_json::from_str(&next_sse_data(&mut events_a).await).expect("valid JSON response");
+        assert!(
+            init_response.get("result").is_some(),
+            "session A's initialize should succeed: {init_response:?}"
+        );
+
+        // Session B never called initialize; if it shared AppState with
+        // session A (the bug these transports were rewritten to fix) this
+        // would succeed instead of being rejected.
+        let list_body = json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list", "params": {} });
+        client
+            .post(format!("http://{bind_addr}/rpc?session={session_b}"))
+            .body(list_body.to_string())
+            .send()
+            .await
+            .expect("POST /rpc should succeed");
+        let list_response: Value =
+            serde_json::from_str(&next_sse_data(&mut events_b).await).expect("valid JSON response");
+        assert_eq!(
+            list_response["error"]["code"], -32002,
+            "session B must still be uninitialized: {list_response:?}"
+        );
+    }
+}
@@ -1,35 +1,186 @@
+mod daemon;
+mod http;
 mod stdio;
+mod unix;
+mod websocket;
 
 use async_trait::async_trait;
-use std::path::PathBuf;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::config::TransportConfig;
+use crate::config::{FramingKind, TransportConfig};
 
+pub use daemon::serve as serve_daemon;
+pub use http::serve as serve_http;
 pub use stdio::StdioTransport;
+pub use unix::UnixSocketTransport;
+pub use websocket::WebSocketTransport;
 
 /// Abstract interface for the JSON-RPC transport used by the server.
+///
+/// Implementing this trait is how gateways (Unix socket, WebSocket, and the
+/// HTTP/SSE gateway's per-session transports) plug into the server without
+/// `Server` knowing how bytes actually move.
 #[async_trait]
 pub trait Transport: Send {
     /// Reads the next complete JSON-RPC payload.
     async fn read(&mut self) -> anyhow::Result<Option<String>>;
     /// Writes a JSON-RPC payload to the peer.
     async fn write(&mut self, payload: &str) -> anyhow::Result<()>;
+    /// Splits this transport into independent read/write halves.
+    ///
+    /// The server reads the next frame while a writer task flushes a
+    /// previously-computed response, so framed output from concurrently
+    /// dispatched requests never interleaves on the wire.
+    fn split(self: Box<Self>) -> (BoxTransportReader, BoxTransportWriter);
+}
+
+/// The read half produced by [`Transport::split`].
+#[async_trait]
+pub trait TransportReader: Send {
+    async fn read(&mut self) -> anyhow::Result<Option<String>>;
+}
+
+/// The write half produced by [`Transport::split`].
+#[async_trait]
+pub trait TransportWriter: Send {
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()>;
 }
 
 /// Helper alias for boxed transport trait objects with the right bounds.
 pub type BoxTransport = Box<dyn Transport + Send>;
+pub type BoxTransportReader = Box<dyn TransportReader + Send>;
+pub type BoxTransportWriter = Box<dyn TransportWriter + Send>;
 
-/// Factory to create the desired transport from configuration.
+/// Factory to create a single-connection transport from configuration.
+///
+/// `TransportConfig::Daemon`/`TransportConfig::Http` aren't single-connection
+/// transports — they accept any number of concurrent clients, each of which
+/// needs its own `Server` (see `serve_daemon`/`serve_http`) rather than a
+/// single `BoxTransport` shared by everyone, so `main` handles those two
+/// variants separately instead of calling this factory.
 pub async fn create_transport(config: &TransportConfig) -> anyhow::Result<BoxTransport> {
     let transport: BoxTransport = match config {
-        TransportConfig::Stdio => Box::new(StdioTransport::new()),
-        TransportConfig::UnixSocket(path) => {
-            let path: PathBuf = path.clone();
+        TransportConfig::Stdio { framing } => Box::new(StdioTransport::new(*framing).await?),
+        TransportConfig::UnixSocket(path) => Box::new(UnixSocketTransport::bind(path).await?),
+        TransportConfig::WebSocket(bind_addr) => {
+            Box::new(WebSocketTransport::bind(*bind_addr).await?)
+        }
+        TransportConfig::Http(_) | TransportConfig::Daemon(_) => {
             return Err(anyhow::anyhow!(
-                "Unix domain socket transport is not implemented yet (requested path: {path:?})"
+                "multi-client transports are served via serve_http/serve_daemon, not create_transport"
             ));
         }
     };
 
     Ok(transport)
 }
+
+/// Reads one `Content-Length: N\r\n\r\n{body}`-framed payload, shared by every
+/// transport that frames messages the LSP way (stdio, Unix socket).
+pub(crate) async fn read_content_length_frame<R>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+) -> anyhow::Result<Option<String>>
+where
+    R: AsyncBufRead + AsyncRead + Unpin,
+{
+    let mut header_line = String::new();
+    let mut content_length: Option<usize> = None;
+
+    loop {
+        header_line.clear();
+        let bytes = reader.read_line(&mut header_line).await?;
+        if bytes == 0 {
+            return Ok(None);
+        }
+
+        if header_line == "\r\n" {
+            break;
+        }
+
+        let trimmed = header_line.trim();
+        if let Some(rest) = trimmed.strip_prefix("Content-Length:") {
+            content_length = Some(rest.trim().parse()?);
+        }
+    }
+
+    let len = content_length.ok_or_else(|| anyhow::anyhow!("missing Content-Length header"))?;
+    buffer.resize(len, 0);
+    reader.read_exact(buffer).await?;
+
+    let payload = String::from_utf8(buffer.clone())?;
+    buffer.clear();
+    Ok(Some(payload))
+}
+
+/// Writes one `Content-Length: N\r\n\r\n{body}`-framed payload, shared by every
+/// transport that frames messages the LSP way (stdio, Unix socket).
+pub(crate) async fn write_content_length_frame<W>(writer: &mut W, payload: &str) -> anyhow::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let bytes = payload.as_bytes();
+    let header = format!("Content-Length: {}\r\n\r\n", bytes.len());
+    writer.write_all(header.as_bytes()).await?;
+    writer.write_all(bytes).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Reads one newline-delimited JSON payload: a single JSON object per line,
+/// with blank lines skipped.
+pub(crate) async fn read_line_delimited_frame<R>(reader: &mut R) -> anyhow::Result<Option<String>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    loop {
+        let mut line = String::new();
+        let bytes = reader.read_line(&mut line).await?;
+        if bytes == 0 {
+            return Ok(None);
+        }
+
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        if trimmed.is_empty() {
+            continue;
+        }
+        return Ok(Some(trimmed.to_string()));
+    }
+}
+
+/// Writes one newline-delimited JSON payload. Callers serialize compactly so
+/// the payload never contains an embedded raw newline.
+pub(crate) async fn write_line_delimited_frame<W>(writer: &mut W, payload: &str) -> anyhow::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    writer.write_all(payload.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Auto-detects framing mode by peeking (without consuming) the first
+/// non-whitespace byte: `C` implies `Content-Length` headers, `{`/`[` implies
+/// line-delimited JSON. Defaults to `Content-Length` on anything else or EOF.
+pub(crate) async fn detect_framing<R>(reader: &mut R) -> anyhow::Result<FramingKind>
+where
+    R: AsyncBufRead + Unpin,
+{
+    loop {
+        let buf = reader.fill_buf().await?;
+        let Some(&first) = buf.first() else {
+            return Ok(FramingKind::ContentLength);
+        };
+
+        if first.is_ascii_whitespace() {
+            reader.consume(1);
+            continue;
+        }
+
+        return Ok(match first {
+            b'{' | b'[' => FramingKind::LineDelimited,
+            _ => FramingKind::ContentLength,
+        });
+    }
+}
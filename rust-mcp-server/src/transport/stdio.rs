@@ -1,68 +1,108 @@
 use async_trait::async_trait;
-use tokio::io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Stdin, Stdout};
+use tokio::io::{self, BufReader, Stdin, Stdout};
 
-use super::Transport;
+use crate::config::FramingKind;
 
-/// Transport implementation that uses stdin/stdout with Content-Length framing.
+use super::{BoxTransportReader, BoxTransportWriter, Transport, TransportReader, TransportWriter};
+use super::{
+    detect_framing, read_content_length_frame, read_line_delimited_frame,
+    write_content_length_frame, write_line_delimited_frame,
+};
+
+/// Transport implementation that uses stdin/stdout, framed either with
+/// `Content-Length` headers or as newline-delimited JSON.
 pub struct StdioTransport {
     reader: BufReader<Stdin>,
     writer: Stdout,
     buffer: Vec<u8>,
+    framing: FramingKind,
 }
 
 impl StdioTransport {
-    pub fn new() -> Self {
-        Self {
-            reader: BufReader::new(io::stdin()),
+    /// Uses `framing` if given, otherwise peeks the first non-whitespace
+    /// byte on stdin to decide between `Content-Length` and line-delimited
+    /// framing.
+    pub async fn new(framing: Option<FramingKind>) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(io::stdin());
+        let framing = match framing {
+            Some(framing) => framing,
+            None => detect_framing(&mut reader).await?,
+        };
+
+        Ok(Self {
+            reader,
             writer: io::stdout(),
             buffer: Vec::with_capacity(8 * 1024),
-        }
+            framing,
+        })
     }
 }
 
 #[async_trait]
 impl Transport for StdioTransport {
     async fn read(&mut self) -> anyhow::Result<Option<String>> {
-        let mut header_line = String::new();
-        let mut content_length: Option<usize> = None;
-
-        loop {
-            header_line.clear();
-            let bytes = self.reader.read_line(&mut header_line).await?;
-            if bytes == 0 {
-                // EOF encountered.
-                return Ok(None);
-            }
-
-            if header_line == "\r\n" {
-                break;
+        match self.framing {
+            FramingKind::ContentLength => {
+                read_content_length_frame(&mut self.reader, &mut self.buffer).await
             }
+            FramingKind::LineDelimited => read_line_delimited_frame(&mut self.reader).await,
+        }
+    }
 
-            let trimmed = header_line.trim();
-            if let Some(rest) = trimmed.strip_prefix("Content-Length:") {
-                content_length = Some(rest.trim().parse()?);
-            }
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        match self.framing {
+            FramingKind::ContentLength => write_content_length_frame(&mut self.writer, payload).await,
+            FramingKind::LineDelimited => write_line_delimited_frame(&mut self.writer, payload).await,
         }
+    }
 
-        let len = content_length.ok_or_else(|| anyhow::anyhow!("missing Content-Length header"))?;
-        self.buffer.resize(len, 0);
-        self.reader.read_exact(&mut self.buffer).await?;
+    fn split(self: Box<Self>) -> (BoxTransportReader, BoxTransportWriter) {
+        let Self {
+            reader,
+            writer,
+            buffer,
+            framing,
+        } = *self;
+        (
+            Box::new(StdinReader {
+                reader,
+                buffer,
+                framing,
+            }),
+            Box::new(StdoutWriter { writer, framing }),
+        )
+    }
+}
 
-        // Consume the trailing CRLF after the JSON payload per header-based framing convention.
-        let mut trailing = [0u8; 2];
-        self.reader.read_exact(&mut trailing).await?;
+struct StdinReader {
+    reader: BufReader<Stdin>,
+    buffer: Vec<u8>,
+    framing: FramingKind,
+}
 
-        let payload = String::from_utf8(self.buffer.clone())?;
-        self.buffer.clear();
-        Ok(Some(payload))
+#[async_trait]
+impl TransportReader for StdinReader {
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        match self.framing {
+            FramingKind::ContentLength => {
+                read_content_length_frame(&mut self.reader, &mut self.buffer).await
+            }
+            FramingKind::LineDelimited => read_line_delimited_frame(&mut self.reader).await,
+        }
     }
+}
+
+struct StdoutWriter {
+    writer: Stdout,
+    framing: FramingKind,
+}
 
+#[async_trait]
+impl TransportWriter for StdoutWriter {
     async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
-        let bytes = payload.as_bytes();
-        let header = format!("Content-Length: {}\r\n\r\n", bytes.len());
-        self.writer.write_all(header.as_bytes()).await?;
-        self.writer.write_all(bytes).await?;
-        self.writer.flush().await?;
-        Ok(())
+        match self.framing {
+            FramingKind::ContentLength => write_content_length_frame(&mut self.writer, payload).await,
+            FramingKind::LineDelimited => write_line_delimited_frame(&mut self.writer, payload).await,
+        }
     }
 }
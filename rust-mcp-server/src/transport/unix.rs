@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use tokio::io::BufReader;
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tracing::info;
+
+use super::{BoxTransportReader, BoxTransportWriter, Transport, TransportReader, TransportWriter};
+use super::{read_content_length_frame, write_content_length_frame};
+
+/// Transport implementation that listens on a Unix domain socket and frames
+/// messages the same way `StdioTransport` does (`Content-Length` headers).
+pub struct UnixSocketTransport {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    buffer: Vec<u8>,
+}
+
+impl UnixSocketTransport {
+    /// Binds `socket_path` and blocks until a single client connects.
+    ///
+    /// Removes a stale socket file left behind by a previous run before
+    /// binding, mirroring how most Unix-socket servers handle restarts.
+    pub async fn bind(socket_path: &Path) -> anyhow::Result<Self> {
+        if socket_path.exists() {
+            std::fs::remove_file(socket_path)?;
+        }
+
+        let listener = UnixListener::bind(socket_path)?;
+        info!(path = %socket_path.display(), "waiting for a client to connect");
+        let (stream, _addr) = listener.accept().await?;
+        info!(path = %socket_path.display(), "client connected");
+
+        Ok(Self::from_stream(stream))
+    }
+
+    fn from_stream(stream: UnixStream) -> Self {
+        let (read_half, write_half) = stream.into_split();
+        Self {
+            reader: BufReader::new(read_half),
+            writer: write_half,
+            buffer: Vec::with_capacity(8 * 1024),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for UnixSocketTransport {
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        read_content_length_frame(&mut self.reader, &mut self.buffer).await
+    }
+
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        write_content_length_frame(&mut self.writer, payload).await
+    }
+
+    fn split(self: Box<Self>) -> (BoxTransportReader, BoxTransportWriter) {
+        let Self {
+            reader,
+            writer,
+            buffer,
+        } = *self;
+        (
+            Box::new(UnixSocketReader { reader, buffer }),
+            Box::new(UnixSocketWriter { writer }),
+        )
+    }
+}
+
+struct UnixSocketReader {
+    reader: BufReader<OwnedReadHalf>,
+    buffer: Vec<u8>,
+}
+
+#[async_trait]
+impl TransportReader for UnixSocketReader {
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        read_content_length_frame(&mut self.reader, &mut self.buffer).await
+    }
+}
+
+struct UnixSocketWriter {
+    writer: OwnedWriteHalf,
+}
+
+#[async_trait]
+impl TransportWriter for UnixSocketWriter {
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        write_content_length_frame(&mut self.writer, payload).await
+    }
+}
@@ -0,0 +1,91 @@
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::info;
+
+use super::{BoxTransportReader, BoxTransportWriter, Transport, TransportReader, TransportWriter};
+
+/// Transport implementation that carries JSON-RPC frames as WebSocket text
+/// messages, so browser-based or networked MCP clients can connect without
+/// spawning the server binary as a subprocess.
+pub struct WebSocketTransport {
+    stream: WebSocketStream<TcpStream>,
+}
+
+impl WebSocketTransport {
+    /// Binds `bind_addr` and blocks until a single client completes the
+    /// WebSocket handshake.
+    pub async fn bind(bind_addr: SocketAddr) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(bind_addr).await?;
+        info!(%bind_addr, "waiting for a WebSocket client to connect");
+        let (tcp_stream, peer_addr) = listener.accept().await?;
+        let stream = tokio_tungstenite::accept_async(tcp_stream).await?;
+        info!(%peer_addr, "WebSocket client connected");
+
+        Ok(Self { stream })
+    }
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        read_next_message(&mut self.stream).await
+    }
+
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        self.stream.send(Message::Text(payload.to_string())).await?;
+        Ok(())
+    }
+
+    fn split(self: Box<Self>) -> (BoxTransportReader, BoxTransportWriter) {
+        let (sink, stream) = self.stream.split();
+        (
+            Box::new(WebSocketReader { stream }),
+            Box::new(WebSocketWriter { sink }),
+        )
+    }
+}
+
+struct WebSocketReader {
+    stream: SplitStream<WebSocketStream<TcpStream>>,
+}
+
+#[async_trait]
+impl TransportReader for WebSocketReader {
+    async fn read(&mut self) -> anyhow::Result<Option<String>> {
+        read_next_message(&mut self.stream).await
+    }
+}
+
+struct WebSocketWriter {
+    sink: SplitSink<WebSocketStream<TcpStream>, Message>,
+}
+
+#[async_trait]
+impl TransportWriter for WebSocketWriter {
+    async fn write(&mut self, payload: &str) -> anyhow::Result<()> {
+        self.sink.send(Message::Text(payload.to_string())).await?;
+        Ok(())
+    }
+}
+
+async fn read_next_message<S>(stream: &mut S) -> anyhow::Result<Option<String>>
+where
+    S: futures_util::Stream<Item = tokio_tungstenite::tungstenite::Result<Message>> + Unpin,
+{
+    loop {
+        match stream.next().await {
+            None => return Ok(None),
+            Some(Ok(Message::Text(text))) => return Ok(Some(text)),
+            Some(Ok(Message::Binary(bytes))) => return Ok(Some(String::from_utf8(bytes)?)),
+            Some(Ok(Message::Close(_))) => return Ok(None),
+            Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_))) => continue,
+            Some(Err(err)) => return Err(err.into()),
+        }
+    }
+}